@@ -0,0 +1,63 @@
+use crate::utils::{FetchError, FetchedPage};
+use reqwest::Url;
+use serde_json::Value;
+use std::io;
+
+const MAX_CONTINUATIONS: usize = 10; // The API returns at most 500 links per request
+
+// Lists a page's article links through the MediaWiki API (action=query&prop=links),
+// independent of how the rendered HTML happens to be marked up. Every request, continuations
+// included, goes through `fetch` so the caller can rate limit each one
+pub fn fetch_api_links(
+    page_url: &Url,
+    title: &str,
+    mut fetch: impl FnMut(&str) -> Result<FetchedPage, FetchError>,
+) -> Result<Vec<String>, FetchError> {
+    let endpoint = format!(
+        "https://{}/w/api.php",
+        page_url.host_str().unwrap_or_default()
+    );
+    let mut titles = Vec::new();
+    let mut continuation: Option<String> = None;
+
+    for _ in 0..MAX_CONTINUATIONS {
+        let mut params = vec![
+            ("action", "query"),
+            ("format", "json"),
+            ("formatversion", "2"),
+            ("prop", "links"),
+            ("plnamespace", "0"),
+            ("pllimit", "max"),
+            ("titles", title),
+        ];
+        if let Some(token) = &continuation {
+            params.push(("plcontinue", token));
+        }
+        let url = Url::parse_with_params(&endpoint, &params)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let page = fetch(url.as_str())?;
+        let response: Value = serde_json::from_str(&page.body).map_err(io::Error::from)?;
+        titles.extend(parse_link_titles(&response));
+
+        continuation = response["continue"]["plcontinue"]
+            .as_str()
+            .map(|token| token.to_string());
+        if continuation.is_none() {
+            break;
+        }
+    }
+
+    Ok(titles)
+}
+
+fn parse_link_titles(response: &Value) -> Vec<String> {
+    response["query"]["pages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|page| page["links"].as_array().into_iter().flatten())
+        .filter_map(|link| link["title"].as_str())
+        .map(|title| title.to_string())
+        .collect()
+}
@@ -0,0 +1,222 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Serialize)]
+pub struct BreakerConfig {
+    pub failure_threshold: f64, // Fraction of recent requests that must fail to open
+    pub window: usize,          // Number of recent requests considered
+    pub cooldown: Duration,
+    pub max_reopens: usize, // Consecutive failed probes before the site is treated as down
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 0.5,
+            window: 20,
+            cooldown: Duration::from_secs(30),
+            max_reopens: 5,
+        }
+    }
+}
+
+enum BreakerState {
+    Closed,
+    Open { until: Instant },
+    HalfOpen, // A single probe request is in flight
+}
+
+// Shared by every worker so an outage makes the whole crawl fail fast instead of each request
+// timing out in turn
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    inner: Mutex<BreakerInner>,
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    outcomes: VecDeque<bool>,
+    reopens: usize, // Failed probes since the breaker last closed
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                outcomes: VecDeque::new(),
+                reopens: 0,
+            }),
+        }
+    }
+
+    // Err carries how long to wait before trying again
+    pub fn allow(&self) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    Err(until - now)
+                } else {
+                    inner.state = BreakerState::HalfOpen;
+                    Ok(())
+                }
+            }
+            BreakerState::HalfOpen => Err(self.config.cooldown / 10),
+        }
+    }
+
+    pub fn record(&self, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let BreakerInner {
+            state,
+            outcomes,
+            reopens,
+        } = &mut *inner;
+
+        if let BreakerState::HalfOpen = state {
+            if success {
+                eprintln!("Circuit breaker closed: probe request succeeded");
+                *state = BreakerState::Closed;
+                outcomes.clear();
+                *reopens = 0;
+            } else {
+                *state = BreakerState::Open {
+                    until: Instant::now() + self.config.cooldown,
+                };
+                *reopens += 1;
+            }
+            return;
+        }
+
+        outcomes.push_back(success);
+        if outcomes.len() > self.config.window {
+            outcomes.pop_front();
+        }
+
+        let failures = outcomes.iter().filter(|success| !**success).count();
+        if outcomes.len() >= self.config.window
+            && failures as f64 / outcomes.len() as f64 >= self.config.failure_threshold
+        {
+            eprintln!(
+                "Circuit breaker opened: {} of the last {} requests failed, pausing for {}s",
+                failures,
+                outcomes.len(),
+                self.config.cooldown.as_secs()
+            );
+            *state = BreakerState::Open {
+                until: Instant::now() + self.config.cooldown,
+            };
+            outcomes.clear();
+        }
+    }
+
+    // True once enough probes in a row have failed that waiting out more cooldowns is pointless
+    pub fn gave_up(&self) -> bool {
+        self.config.max_reopens > 0 && self.inner.lock().unwrap().reopens >= self.config.max_reopens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const COOLDOWN: Duration = Duration::from_millis(20);
+
+    fn breaker(max_reopens: usize) -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            failure_threshold: 0.5,
+            window: 4,
+            cooldown: COOLDOWN,
+            max_reopens,
+        })
+    }
+
+    fn open(breaker: &CircuitBreaker) {
+        for _ in 0..4 {
+            breaker.record(false);
+        }
+        assert!(breaker.allow().is_err());
+    }
+
+    // Waits out the cooldown and lets the probe through
+    fn probe(breaker: &CircuitBreaker) {
+        thread::sleep(COOLDOWN + Duration::from_millis(10));
+        assert!(breaker.allow().is_ok());
+    }
+
+    #[test]
+    fn opens_once_the_window_reaches_the_failure_threshold() {
+        let breaker = breaker(5);
+        for success in [true, false, false] {
+            breaker.record(success);
+            assert!(breaker.allow().is_ok());
+        }
+        breaker.record(true);
+        // 2 of the last 4 failed
+        assert!(breaker.allow().is_err());
+    }
+
+    #[test]
+    fn stays_closed_below_the_threshold() {
+        let breaker = breaker(5);
+        for success in [false, true, true, true, false, true, true] {
+            breaker.record(success);
+            assert!(breaker.allow().is_ok());
+        }
+    }
+
+    #[test]
+    fn half_open_lets_a_single_probe_through() {
+        let breaker = breaker(5);
+        open(&breaker);
+        probe(&breaker);
+        assert_eq!(breaker.allow(), Err(COOLDOWN / 10));
+        assert_eq!(breaker.allow(), Err(COOLDOWN / 10));
+    }
+
+    #[test]
+    fn successful_probe_closes_the_breaker() {
+        let breaker = breaker(5);
+        open(&breaker);
+        probe(&breaker);
+        breaker.record(true);
+        assert!(breaker.allow().is_ok());
+        assert!(breaker.allow().is_ok());
+        assert!(!breaker.gave_up());
+    }
+
+    #[test]
+    fn gives_up_after_max_reopens_failed_probes() {
+        let breaker = breaker(2);
+        open(&breaker);
+        probe(&breaker);
+        breaker.record(false);
+        assert!(breaker.allow().is_err());
+        assert!(!breaker.gave_up());
+        probe(&breaker);
+        breaker.record(false);
+        assert!(breaker.gave_up());
+    }
+
+    #[test]
+    fn a_successful_probe_resets_the_reopen_count() {
+        let breaker = breaker(2);
+        open(&breaker);
+        probe(&breaker);
+        breaker.record(false);
+        probe(&breaker);
+        breaker.record(true);
+
+        open(&breaker);
+        probe(&breaker);
+        breaker.record(false);
+        assert!(!breaker.gave_up());
+    }
+}
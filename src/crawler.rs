@@ -1,74 +1,1038 @@
+use crate::api_links::fetch_api_links;
+use crate::config::CrawlerConfig;
+use crate::filter::{LinkDecision, RejectReason, TitlePatternMode, URLFilter};
+use crate::frontier::FrontierStore;
+use crate::rate_limit::{DomainSlots, RateLimiter};
+use crate::robots::fetch_robots_rules;
 use crate::stats::CrawlStats;
-use crate::utils::fetch_page;
-use crossbeam::queue::SegQueue;
-use scraper::{Html, Selector};
+use crate::timing::{Phase, PhaseTimers};
+use crate::trap::TrapDetector;
+use crate::utils::{build_client, fetch_page, url_to_title, FetchError, HttpClient};
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const MAX_DEPTH: usize = 3;
-const RATE_LIMIT: u64 = 200;
+const SEE_ALSO_MAX_DEPTH: usize = 6; // See also graphs are sparse enough to go deeper
+const HTML_CONTENT_TYPES: [&str; 2] = ["text/html", "application/xhtml+xml"];
+const ARTICLE_SKELETON: &str = "#mw-content-text, body.mediawiki";
+const PROSE_SELECTOR: &str = ".mw-parser-output > p";
+const SEE_ALSO_IDS: [&str; 2] = ["See_also", "See_Also"];
+const RATE_LIMIT: u64 = 200; // Minimum delay between requests to the same domain
+const IDLE_POLL: u64 = 50; // How long an idle worker waits for others to discover URLs
+const NUM_WORKERS: usize = 4;
+
+#[derive(Debug, PartialEq)]
+pub enum CrawlOutcome {
+    // Workers stopped on their page budget with URLs still pending
+    BudgetExhausted,
+    // Every URL reachable within the depth limit under the filter has been processed
+    FixedPoint,
+    // The circuit breaker kept re-opening, so the rest of the queue was left for a later session
+    SiteUnavailable,
+}
+
+impl fmt::Display for CrawlOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrawlOutcome::BudgetExhausted => write!(f, "page budget exhausted"),
+            CrawlOutcome::FixedPoint => write!(f, "reachable pages fully explored"),
+            CrawlOutcome::SiteUnavailable => write!(f, "site unavailable"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkScope {
+    // Every link matched by the link selectors
+    All,
+    // Only links in the "See also" section
+    SeeAlso,
+    // Only links in the lead, before the first section heading
+    Lead,
+}
+
+impl LinkScope {
+    pub fn default_max_depth(self) -> usize {
+        match self {
+            LinkScope::All | LinkScope::Lead => MAX_DEPTH,
+            LinkScope::SeeAlso => SEE_ALSO_MAX_DEPTH,
+        }
+    }
+}
+
+impl FromStr for LinkScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(LinkScope::All),
+            "see-also" => Ok(LinkScope::SeeAlso),
+            "lead" => Ok(LinkScope::Lead),
+            other => Err(format!(
+                "Unknown link scope '{}' (expected all, see-also or lead)",
+                other
+            )),
+        }
+    }
+}
+
+struct CrawlContext {
+    queue: Arc<FrontierStore>,
+    visited: Arc<Mutex<Vec<String>>>,
+    stats: Arc<Mutex<CrawlStats>>,
+    filter: Arc<URLFilter>,
+    config: Arc<CrawlerConfig>,
+    client: HttpClient,
+    rate_limiter: RateLimiter,
+    domain_slots: DomainSlots,
+    // Every URL ever recorded in `visited`, for constant-time duplicate checks at enqueue time
+    known: Mutex<HashSet<String>>,
+    // None when spider trap detection is disabled
+    traps: Option<Mutex<TrapDetector>>,
+    // Minimum depth each queued URL was discovered at, persisted across sessions
+    depths: Arc<Mutex<HashMap<String, usize>>>,
+    claims: Claims,
+    // Pages fetched and parsed successfully, across sessions (`visited` also holds pages only linked to)
+    fetched: Arc<Mutex<HashSet<String>>>,
+    // URLs queued or being processed; when it drops to zero nothing new can be discovered
+    pending: AtomicUsize,
+    // Set when the circuit breaker gives up; workers stop and leave the queue for a later session
+    site_unavailable: AtomicBool,
+    timers: Vec<PhaseTimers>, // One per worker
+}
 
 pub fn start_crawl(
-    queue: &Arc<SegQueue<(String, usize)>>,
+    queue: &Arc<FrontierStore>,
     visited: &Arc<Mutex<Vec<String>>>,
+    depths: &Arc<Mutex<HashMap<String, usize>>>,
+    fetched: &Arc<Mutex<HashSet<String>>>,
     stats: &Arc<Mutex<CrawlStats>>,
-) {
-    let handles: Vec<_> = (0..4)
-        .map(|_| {
-            let queue_clone = Arc::clone(queue);
-            let visited_clone = Arc::clone(visited);
-            let stats_clone = Arc::clone(stats);
+    filter: &Arc<URLFilter>,
+    config: &Arc<CrawlerConfig>,
+) -> CrawlOutcome {
+    let client = build_client(
+        &config.user_agent(),
+        config.proxy.as_ref(),
+        config.breaker,
+        &config.extra_headers,
+        config.max_redirects,
+    )
+    .expect("Failed to build HTTP client");
+    let rate_limit = Duration::from_millis(RATE_LIMIT);
+    let mut rate_limiter = RateLimiter::new(rate_limit, config.jitter_fraction);
+    for domain in &config.domains {
+        let rules = fetch_robots_rules(&client, domain, config.max_page_bytes);
+        if let Some(crawl_delay) = rules.crawl_delay.filter(|delay| *delay > rate_limit) {
+            println!(
+                "Enforcing robots.txt Crawl-delay of {:.1}s for {}",
+                crawl_delay.as_secs_f64(),
+                domain
+            );
+            rate_limiter.set_interval(domain, crawl_delay);
+        }
+    }
+
+    let context = Arc::new(CrawlContext {
+        queue: Arc::clone(queue),
+        visited: Arc::clone(visited),
+        stats: Arc::clone(stats),
+        filter: Arc::clone(filter),
+        config: Arc::clone(config),
+        client,
+        rate_limiter,
+        domain_slots: DomainSlots::new(config.max_requests_per_domain),
+        known: Mutex::new(visited.lock().unwrap().iter().cloned().collect()),
+        traps: (config.spider_trap_threshold > 0)
+            .then(|| Mutex::new(TrapDetector::new(config.spider_trap_threshold))),
+        depths: Arc::clone(depths),
+        claims: Claims::default(),
+        fetched: Arc::clone(fetched),
+        pending: AtomicUsize::new(queue.len()),
+        site_unavailable: AtomicBool::new(false),
+        timers: (0..NUM_WORKERS).map(|_| PhaseTimers::default()).collect(),
+    });
+
+    let handles: Vec<_> = (0..NUM_WORKERS)
+        .map(|worker| {
+            let context = Arc::clone(&context);
 
             thread::spawn(move || {
                 let mut local_visited_count = 0;
                 while local_visited_count < 10 {
-                    let (current_url, depth) = match queue_clone.pop() {
-                        Some((url, depth)) => (url, depth),
-                        None => break,
+                    if context.site_unavailable.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let (current_url, depth) = match context.queue.pop() {
+                        Some(item) => item,
+                        None if context.pending.load(Ordering::SeqCst) == 0 => break,
+                        None => {
+                            // Other workers may still discover URLs from pages in flight
+                            context.timers[worker].time(Phase::QueueIdle, || {
+                                thread::sleep(Duration::from_millis(IDLE_POLL))
+                            });
+                            continue;
+                        }
                     };
 
-                    if depth > MAX_DEPTH {
-                        continue;
+                    if process_page(&context, worker, &current_url, depth) {
+                        local_visited_count += 1;
                     }
+                    context.pending.fetch_sub(1, Ordering::SeqCst);
+                }
+                local_visited_count
+            })
+        })
+        .collect();
 
-                    match fetch_page(&current_url) {
-                        Ok(body) => {
-                            let document = Html::parse_document(&body);
-                            let link_selector = Selector::parse("a").unwrap();
-                            let mut visited_guard = visited_clone.lock().unwrap();
-                            let mut stats_guard = stats_clone.lock().unwrap();
-
-                            for element in document.select(&link_selector) {
-                                if let Some(href) = element.value().attr("href") {
-                                    let href = href.to_string();
-                                    if href.starts_with("/wiki/") && !visited_guard.contains(&href)
-                                    {
-                                        let full_url = format!("https://en.wikipedia.org{}", href);
-                                        queue_clone.push((full_url.clone(), depth + 1));
-                                        visited_guard.push(full_url.clone());
-                                        stats_guard.links_followed += 1;
-                                    } else {
-                                        stats_guard.links_ignored += 1;
-                                    }
-                                }
-                            }
+    let worker_pages: Vec<usize> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    stats.lock().unwrap().timings = context
+        .timers
+        .iter()
+        .zip(worker_pages)
+        .enumerate()
+        .map(|(worker, (timers, pages))| timers.snapshot(worker, pages))
+        .collect();
 
-                            stats_guard.pages_visited += 1;
-                            local_visited_count += 1;
-                        }
-                        Err(_) => {
-                            eprintln!("Failed to fetch {}", current_url);
-                        }
+    if context.site_unavailable.load(Ordering::SeqCst) {
+        CrawlOutcome::SiteUnavailable
+    } else if context.pending.load(Ordering::SeqCst) == 0 {
+        CrawlOutcome::FixedPoint
+    } else {
+        CrawlOutcome::BudgetExhausted
+    }
+}
+
+// Links the crawl would record; excluded title patterns are dropped, so they don't count
+fn has_valid_link(links: &[LinkDecision]) -> bool {
+    links.iter().any(|link| {
+        matches!(
+            link,
+            LinkDecision::Follow(_)
+                | LinkDecision::TitlePattern(
+                    TitlePatternMode::Include | TitlePatternMode::NoExpand,
+                    _
+                )
+        )
+    })
+}
+
+// Depth each URL was fetched (or is being fetched) at in this session, so duplicate queue
+// entries are never fetched twice unless they reach the page at a smaller depth
+#[derive(Default)]
+struct Claims {
+    claimed: Mutex<HashMap<String, usize>>,
+}
+
+impl Claims {
+    // None when the URL is already claimed at this depth or a smaller one, otherwise the depth
+    // of the claim this one replaced
+    fn claim(&self, url: &str, depth: usize) -> Option<Option<usize>> {
+        let mut claimed = self.claimed.lock().unwrap();
+        match claimed.get(url) {
+            Some(&claimed_depth) if claimed_depth <= depth => None,
+            previous => {
+                let previous = previous.copied();
+                claimed.insert(url.to_string(), depth);
+                Some(previous)
+            }
+        }
+    }
+
+    // Puts back the claim that `claim` replaced, if any
+    fn release(&self, url: &str, previous: Option<usize>) {
+        let mut claimed = self.claimed.lock().unwrap();
+        match previous {
+            Some(previous_depth) => claimed.insert(url.to_string(), previous_depth),
+            None => claimed.remove(url),
+        };
+    }
+}
+
+// Returns true when the page was fetched and its links recorded
+fn process_page(context: &CrawlContext, worker: usize, current_url: &str, depth: usize) -> bool {
+    let timers = &context.timers[worker];
+
+    if depth > context.config.depth_limit() {
+        return false;
+    }
+
+    let previous_claim = match context.claims.claim(current_url, depth) {
+        Some(previous_claim) => previous_claim,
+        None => return false,
+    };
+
+    let page_url = match Url::parse(current_url) {
+        Ok(url) => url,
+        Err(err) => {
+            eprintln!("Invalid URL {}: {}", current_url, err);
+            return false;
+        }
+    };
+    let domain = page_url.host_str().unwrap_or_default().to_string();
+    // Taken before the rate limiter so request spacing counts from when a request can really go out
+    let slot = timers.time(Phase::DomainSlotWait, || {
+        context.domain_slots.acquire(&domain)
+    });
+    timers.time(Phase::RateLimitWait, || context.rate_limiter.wait(&domain));
+    let fetched = timers.time(Phase::Network, || {
+        fetch_page(&context.client, current_url, context.config.max_page_bytes)
+    });
+    drop(slot);
+    let page = match fetched {
+        Ok(page) => page,
+        Err(FetchError::TooLarge(bytes)) => {
+            eprintln!("Skipping {}: page too large ({} bytes)", current_url, bytes);
+            context.stats.lock().unwrap().pages_skipped_too_large += 1;
+            return false;
+        }
+        Err(err) => {
+            // Release the claim so a later copy of this URL can retry it
+            context.claims.release(current_url, previous_claim);
+
+            if let FetchError::CircuitOpen(retry_after) = err {
+                // Not the page's fault: put it back and wait out the cooldown
+                context.stats.lock().unwrap().requests_short_circuited += 1;
+                context.pending.fetch_add(1, Ordering::SeqCst);
+                context.queue.push((current_url.to_string(), depth));
+                if context.client.site_unavailable() {
+                    if !context.site_unavailable.swap(true, Ordering::SeqCst) {
+                        eprintln!("Circuit breaker gave up after repeated failed probes, stopping");
                     }
+                } else {
+                    thread::sleep(retry_after);
+                }
+            } else {
+                eprintln!("Failed to fetch {}: {}", current_url, err);
+                *context
+                    .stats
+                    .lock()
+                    .unwrap()
+                    .fetch_errors
+                    .entry(err.kind())
+                    .or_insert(0) += 1;
+            }
+            return false;
+        }
+    };
+
+    // Maintenance pages, captcha interstitials and non-HTML bodies would only yield garbage links
+    if let Some(media_type) = unsupported_media_type(page.content_type.as_deref()) {
+        record_invalid_page(context, current_url, format!("content type {}", media_type));
+        return false;
+    }
+    let parsed = timers.time(Phase::Parsing, || {
+        let document = Html::parse_document(&page.body);
+        has_article_skeleton(&document).then(|| {
+            let prose_length = context
+                .config
+                .min_text_length
+                .map(|_| prose_length(&document));
+            let links = extract_links(
+                &document,
+                &page_url,
+                &context.filter,
+                &context.config.link_selectors,
+                context.config.link_scope,
+            );
+            (prose_length, links)
+        })
+    });
+    let (prose_length, mut links) = match parsed {
+        Some(parsed) => parsed,
+        None => {
+            record_invalid_page(context, current_url, "missing article skeleton".to_string());
+            return false;
+        }
+    };
+    if let (Some(length), Some(min_length)) = (prose_length, context.config.min_text_length) {
+        if length < min_length {
+            // Stubs link mostly to navigation, so their links aren't recorded
+            eprintln!(
+                "Skipping {}: stub with {} characters of prose",
+                current_url, length
+            );
+            context.stats.lock().unwrap().stub_pages_skipped += 1;
+            return false;
+        }
+    }
+
+    // An article with no followable links is more likely a block or error page served with a 200,
+    // or markup the selectors no longer match. Plenty of pages have no See also section, so only
+    // the full-page scope is checked
+    if !has_valid_link(&links) && context.config.link_scope == LinkScope::All {
+        context.stats.lock().unwrap().suspicious_zero_link_pages += 1;
+        eprintln!(
+            "Warning: no valid links found on {}, the page may be an error page or the link selectors may no longer match the page markup",
+            current_url
+        );
+        if context.config.api_link_fallback {
+            if let Some(api_links) = fetch_links_from_api(context, worker, &page_url, &domain) {
+                links = api_links;
+            }
+        }
+    }
+
+    let lock_start = Instant::now();
+    let mut visited_guard = context.visited.lock().unwrap();
+    let mut known_guard = context.known.lock().unwrap();
+    let mut stats_guard = context.stats.lock().unwrap();
+    let mut depths_guard = context.depths.lock().unwrap();
+    let mut traps_guard = context.traps.as_ref().map(|traps| traps.lock().unwrap());
+    timers.record(Phase::LockWait, lock_start.elapsed());
 
-                    thread::sleep(Duration::from_millis(RATE_LIMIT));
+    let (children, out_degree) = record_links(
+        links,
+        depth,
+        &mut visited_guard,
+        &mut known_guard,
+        &mut depths_guard,
+        &mut stats_guard,
+        traps_guard.as_deref_mut(),
+    );
+    for child in children {
+        context.pending.fetch_add(1, Ordering::SeqCst);
+        context.queue.push(child);
+    }
+
+    stats_guard.record_out_degree(current_url, out_degree);
+    record_fetched(
+        &mut context.fetched.lock().unwrap(),
+        &mut stats_guard,
+        current_url,
+    );
+    *stats_guard.pages_per_domain.entry(domain).or_insert(0) += 1;
+    true
+}
+
+// Records the links found on a page at `depth`, returning the (URL, depth) entries to queue and
+// the page's out-degree. A known URL is only queued again when this page reaches it at a smaller
+// depth than before, so pages first reached near the depth limit get expanded once a shorter
+// path to them turns up, in this session or a later one
+fn record_links(
+    links: Vec<LinkDecision>,
+    depth: usize,
+    visited: &mut Vec<String>,
+    known: &mut HashSet<String>,
+    depths: &mut HashMap<String, usize>,
+    stats: &mut CrawlStats,
+    mut traps: Option<&mut TrapDetector>,
+) -> (Vec<(String, usize)>, usize) {
+    let mut children = Vec::new();
+    // Distinct targets, so a page linking the same article from the infobox, body and navbox
+    // counts it once
+    let mut targets = HashSet::new();
+    for link in links {
+        let (full_url, expand) = match link {
+            LinkDecision::Follow(url) => (url, true),
+            LinkDecision::TitlePattern(TitlePatternMode::Include, url) => {
+                stats.title_pattern_included += 1;
+                (url, true)
+            }
+            LinkDecision::TitlePattern(TitlePatternMode::NoExpand, url) => {
+                stats.title_pattern_boundary += 1;
+                (url, false)
+            }
+            LinkDecision::TitlePattern(TitlePatternMode::Exclude, _) => {
+                stats.title_pattern_excluded += 1;
+                stats.links_ignored += 1;
+                continue;
+            }
+            LinkDecision::Noise => {
+                stats.noise_links_ignored += 1;
+                stats.links_ignored += 1;
+                continue;
+            }
+            LinkDecision::Reject(reason) => {
+                if let RejectReason::Namespace(namespace) = &reason {
+                    *stats.namespace_links.entry(namespace.clone()).or_insert(0) += 1;
                 }
-            })
+                *stats
+                    .rejected_links
+                    .entry(reason.label().to_string())
+                    .or_insert(0) += 1;
+                stats.links_ignored += 1;
+                continue;
+            }
+        };
+
+        targets.insert(full_url.clone());
+        let child_depth = depth + 1;
+        if known.contains(&full_url) {
+            let improved = expand
+                && depths
+                    .get(&full_url)
+                    .is_some_and(|&known_depth| child_depth < known_depth);
+            if !improved {
+                stats.duplicate_links_skipped += 1;
+                stats.links_ignored += 1;
+                continue;
+            }
+        } else {
+            if let Some(pattern) = traps
+                .as_mut()
+                .filter(|_| expand)
+                .and_then(|traps| traps.check(&url_to_title(&full_url)))
+            {
+                *stats.spider_trap_links.entry(pattern).or_insert(0) += 1;
+                stats.links_ignored += 1;
+                continue;
+            }
+            known.insert(full_url.clone());
+            visited.push(full_url.clone());
+        }
+
+        if expand {
+            depths.insert(full_url.clone(), child_depth);
+            children.push((full_url, child_depth));
+            stats.links_followed += 1;
+        }
+    }
+    (children, targets.len())
+}
+
+// A page fetched in an earlier session, or earlier in this one, is only being re-expanded at a
+// smaller depth and is not counted as visited again
+fn record_fetched(fetched: &mut HashSet<String>, stats: &mut CrawlStats, url: &str) {
+    if fetched.insert(url.to_string()) {
+        stats.pages_visited += 1;
+    } else {
+        stats.pages_reexpanded += 1;
+    }
+}
+
+fn fetch_links_from_api(
+    context: &CrawlContext,
+    worker: usize,
+    page_url: &Url,
+    domain: &str,
+) -> Option<Vec<LinkDecision>> {
+    let timers = &context.timers[worker];
+    let title = context.filter.page_title(page_url)?;
+
+    let _slot = timers.time(Phase::DomainSlotWait, || {
+        context.domain_slots.acquire(domain)
+    });
+    let result = fetch_api_links(page_url, &title, |url| {
+        timers.time(Phase::RateLimitWait, || context.rate_limiter.wait(domain));
+        timers.time(Phase::Network, || {
+            fetch_page(&context.client, url, context.config.max_page_bytes)
         })
-        .collect();
+    });
+    match result {
+        Ok(titles) => {
+            context.stats.lock().unwrap().api_fallback_pages += 1;
+            Some(
+                titles
+                    .iter()
+                    .map(|title| context.filter.classify_title(page_url, title))
+                    .collect(),
+            )
+        }
+        Err(err) => {
+            eprintln!("API link fallback failed for {}: {}", page_url, err);
+            None
+        }
+    }
+}
+
+// Keeps the claim, so the page is neither retried nor counted as visited
+fn record_invalid_page(context: &CrawlContext, url: &str, reason: String) {
+    eprintln!("Skipping {}: not an article page ({})", url, reason);
+    *context
+        .stats
+        .lock()
+        .unwrap()
+        .invalid_pages
+        .entry(reason)
+        .or_insert(0) += 1;
+}
+
+// The media type of a non-HTML response; a missing Content-Type is given the benefit of the doubt
+fn unsupported_media_type(content_type: Option<&str>) -> Option<String> {
+    content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase())
+        .filter(|media_type| !HTML_CONTENT_TYPES.contains(&media_type.as_str()))
+}
+
+fn has_article_skeleton(document: &Html) -> bool {
+    let skeleton = Selector::parse(ARTICLE_SKELETON).unwrap();
+    document.select(&skeleton).next().is_some()
+}
+
+// Characters of paragraph text in the article body, ignoring infoboxes, navboxes and lists
+fn prose_length(document: &Html) -> usize {
+    let paragraphs = Selector::parse(PROSE_SELECTOR).unwrap();
+    document
+        .select(&paragraphs)
+        .flat_map(|paragraph| paragraph.text())
+        .map(|text| text.trim().chars().count())
+        .sum()
+}
+
+fn extract_links(
+    document: &Html,
+    page_url: &Url,
+    filter: &URLFilter,
+    selectors: &[Selector],
+    scope: LinkScope,
+) -> Vec<LinkDecision> {
+    scoped_roots(document, scope)
+        .into_iter()
+        .flat_map(|root| {
+            selectors
+                .iter()
+                .flat_map(move |selector| root.select(selector))
+        })
+        .filter_map(|element| element.value().attr("href"))
+        .map(|href| filter.classify(page_url, href))
+        .collect()
+}
+
+// Top-level blocks of the article body that fall inside the requested scope
+fn scoped_roots(document: &Html, scope: LinkScope) -> Vec<ElementRef<'_>> {
+    if scope == LinkScope::All {
+        return vec![document.root_element()];
+    }
+
+    let content = Selector::parse(".mw-parser-output").unwrap();
+    let mut roots = Vec::new();
+    for body in document.select(&content) {
+        let mut in_lead = true;
+        let mut in_see_also = false;
+        for child in body.children().filter_map(ElementRef::wrap) {
+            if is_section_heading(child) {
+                in_lead = false;
+                in_see_also = is_see_also_heading(child);
+                continue;
+            }
+            let in_scope = match scope {
+                LinkScope::All => true,
+                LinkScope::SeeAlso => in_see_also,
+                LinkScope::Lead => in_lead,
+            };
+            if in_scope {
+                roots.push(child);
+            }
+        }
+    }
+    roots
+}
+
+// Older skins emit a bare <h2>, newer ones wrap it in <div class="mw-heading mw-heading2">
+fn is_section_heading(element: ElementRef) -> bool {
+    element.value().name() == "h2"
+        || element
+            .value()
+            .classes()
+            .any(|class| class == "mw-heading2")
+}
+
+// The anchor id sits on the <h2> itself or on an inner .mw-headline span depending on the skin
+fn is_see_also_heading(heading: ElementRef) -> bool {
+    let has_see_also_id = std::iter::once(heading)
+        .chain(heading.descendants().filter_map(ElementRef::wrap))
+        .filter_map(|element| element.value().id())
+        .any(|id| SEE_ALSO_IDS.contains(&id));
+    has_see_also_id
+        || heading
+            .text()
+            .collect::<String>()
+            .trim()
+            .eq_ignore_ascii_case("see also")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::DomainRule;
+    use crate::state::CrawlState;
+    use std::collections::VecDeque;
+
+    // A chain of articles, each linking only to the next
+    const CHAIN: [(&str, &str); 4] = [("Seed", "A"), ("A", "B"), ("B", "C"), ("C", "D")];
+
+    fn article(title: &str) -> String {
+        format!("https://en.wikipedia.org/wiki/{}", title)
+    }
+
+    fn test_filter(title_pattern_mode: TitlePatternMode) -> URLFilter {
+        URLFilter::new(
+            vec![DomainRule::new("en.wikipedia.org")],
+            title_pattern_mode,
+            HashSet::new(),
+            true,
+        )
+    }
+
+    struct Session {
+        visited: Vec<String>,
+        depths: HashMap<String, usize>,
+        fetched: HashSet<String>,
+        stats: CrawlStats,
+    }
+
+    impl Session {
+        fn new(
+            visited: Vec<String>,
+            depths: HashMap<String, usize>,
+            fetched: HashSet<String>,
+        ) -> Self {
+            Self {
+                visited,
+                depths,
+                fetched,
+                stats: CrawlStats::new(),
+            }
+        }
+    }
+
+    // process_page without the network: depth check, claim, link recording and fetched set
+    fn run_session(session: &mut Session, seeds: Vec<(String, usize)>, depth_limit: usize) {
+        let filter = test_filter(TitlePatternMode::Exclude);
+        let claims = Claims::default();
+        let mut known: HashSet<String> = session.visited.iter().cloned().collect();
+        let mut queue: VecDeque<(String, usize)> = seeds.into();
+        while let Some((url, depth)) = queue.pop_front() {
+            if depth > depth_limit || claims.claim(&url, depth).is_none() {
+                continue;
+            }
+            let page_url = Url::parse(&url).unwrap();
+            let title = url_to_title(&url);
+            let links = CHAIN
+                .iter()
+                .filter(|(from, _)| *from == title)
+                .map(|(_, to)| filter.classify(&page_url, &format!("/wiki/{}", to)))
+                .collect();
+            let (children, _) = record_links(
+                links,
+                depth,
+                &mut session.visited,
+                &mut known,
+                &mut session.depths,
+                &mut session.stats,
+                None,
+            );
+            queue.extend(children);
+            record_fetched(&mut session.fetched, &mut session.stats, &url);
+        }
+    }
+
+    #[test]
+    fn grandchildren_missed_before_a_resume_are_crawled() {
+        let seed = article("Seed");
+        let mut first = Session::new(
+            vec![seed.clone()],
+            HashMap::from([(seed.clone(), 0)]),
+            HashSet::new(),
+        );
+        run_session(&mut first, vec![(seed.clone(), 0)], 2);
+        assert_eq!(first.stats.pages_visited, 3);
+        assert!(!first.fetched.contains(&article("C")));
+        assert_eq!(first.depths[&article("C")], 3);
+
+        // Resume with a deeper limit; the seed keeps its old budget until it is seeded again
+        let mut state = CrawlState {
+            queue: Vec::new(),
+            queue_segments: Vec::new(),
+            visited: first.visited,
+            depths: first.depths,
+            fetched: first.fetched.into_iter().collect(),
+            user_agent: None,
+            depth_limit: Some(2),
+        };
+        state.rebase_depths(3);
+        assert_eq!(state.depths[&seed], 1);
+        let mut second = Session::new(
+            state.visited,
+            state.depths,
+            state.fetched.into_iter().collect(),
+        );
+        second.depths.insert(seed.clone(), 0);
+        run_session(&mut second, vec![(seed, 0)], 3);
+
+        assert!(second.fetched.contains(&article("C")));
+        assert!(!second.fetched.contains(&article("D")));
+        assert!(second.visited.contains(&article("D")));
+        assert_eq!(second.stats.pages_visited, 1);
+        assert_eq!(second.stats.pages_reexpanded, 3);
+        assert_eq!(second.fetched.len(), 4);
+    }
+
+    #[test]
+    fn title_pattern_modes_decide_what_is_recorded_and_queued() {
+        let page_url = Url::parse(&article("Graph_theory")).unwrap();
+        let list = article("List_of_graph_theory_topics");
+        for (mode, recorded, queued) in [
+            (TitlePatternMode::Exclude, false, false),
+            (TitlePatternMode::NoExpand, true, false),
+            (TitlePatternMode::Include, true, true),
+        ] {
+            let links =
+                vec![test_filter(mode).classify(&page_url, "/wiki/List_of_graph_theory_topics")];
+            let mut visited = Vec::new();
+            let mut depths = HashMap::new();
+            let mut stats = CrawlStats::new();
+            let (children, out_degree) = record_links(
+                links,
+                0,
+                &mut visited,
+                &mut HashSet::new(),
+                &mut depths,
+                &mut stats,
+                None,
+            );
+
+            assert_eq!(visited.contains(&list), recorded, "{:?}", mode);
+            assert_eq!(out_degree, usize::from(recorded), "{:?}", mode);
+            assert_eq!(children.contains(&(list.clone(), 1)), queued, "{:?}", mode);
+            assert_eq!(depths.contains_key(&list), queued, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn excluded_title_patterns_are_not_valid_links() {
+        let list = article("List_of_graph_theory_topics");
+        let excluded = || LinkDecision::TitlePattern(TitlePatternMode::Exclude, list.clone());
+        assert!(!has_valid_link(&[excluded(), LinkDecision::Noise]));
+        assert!(!has_valid_link(&[LinkDecision::Reject(
+            RejectReason::OffDomain
+        )]));
+        assert!(has_valid_link(&[
+            excluded(),
+            LinkDecision::TitlePattern(TitlePatternMode::NoExpand, list.clone()),
+        ]));
+        assert!(has_valid_link(&[
+            excluded(),
+            LinkDecision::TitlePattern(TitlePatternMode::Include, list.clone()),
+        ]));
+        assert!(has_valid_link(&[
+            excluded(),
+            LinkDecision::Follow(article("A"))
+        ]));
+    }
+
+    #[test]
+    fn known_links_are_requeued_only_at_a_smaller_depth() {
+        let url = article("A");
+        let mut visited = vec![url.clone()];
+        let mut known = HashSet::from([url.clone()]);
+        let mut depths = HashMap::from([(url.clone(), 2)]);
+        let mut stats = CrawlStats::new();
+
+        for depth in [1, 2] {
+            let (children, out_degree) = record_links(
+                vec![LinkDecision::Follow(url.clone())],
+                depth,
+                &mut visited,
+                &mut known,
+                &mut depths,
+                &mut stats,
+                None,
+            );
+            assert!(children.is_empty());
+            assert_eq!(out_degree, 1);
+        }
+        let (children, _) = record_links(
+            vec![LinkDecision::Follow(url.clone())],
+            0,
+            &mut visited,
+            &mut known,
+            &mut depths,
+            &mut stats,
+            None,
+        );
+        assert_eq!(children, [(url.clone(), 1)]);
+        assert_eq!(depths[&url], 1);
+        assert_eq!(visited.len(), 1);
+        assert_eq!(stats.duplicate_links_skipped, 2);
+    }
+
+    const OLD_SKIN_PAGE: &str = r#"<html><body><div id="mw-content-text"><div class="mw-parser-output">
+        <p>Lead with a <a href="/wiki/Lead_link">link</a>.</p>
+        <h2><span class="mw-headline" id="History">History</span></h2>
+        <p>Body with a <a href="/wiki/Body_link">link</a>.</p>
+        <h2><span class="mw-headline" id="See_also">See also</span></h2>
+        <ul><li><a href="/wiki/Related_one">One</a></li><li><a href="/wiki/Related_two">Two</a></li></ul>
+        <h2><span class="mw-headline" id="References">References</span></h2>
+        <ol><li><a href="/wiki/Cited_work">Cited</a></li></ol>
+    </div></div></body></html>"#;
+
+    const NEW_SKIN_PAGE: &str = r#"<html><body><div id="mw-content-text"><div class="mw-parser-output">
+        <p>Lead with a <a href="/wiki/Lead_link">link</a>.</p>
+        <div class="mw-heading mw-heading2"><h2 id="History">History</h2><span class="mw-editsection"><a href="/wiki/Edit_history">edit</a></span></div>
+        <p>Body with a <a href="/wiki/Body_link">link</a>.</p>
+        <div class="mw-heading mw-heading2"><h2 id="See_also">See also</h2><span class="mw-editsection"><a href="/wiki/Edit_see_also">edit</a></span></div>
+        <ul><li><a href="/wiki/Related_one">One</a></li><li><a href="/wiki/Related_two">Two</a></li></ul>
+        <div class="mw-heading mw-heading2"><h2 id="References">References</h2></div>
+        <ol><li><a href="/wiki/Cited_work">Cited</a></li></ol>
+    </div></div></body></html>"#;
+
+    const NO_SEE_ALSO_PAGE: &str = r#"<html><body><div id="mw-content-text"><div class="mw-parser-output">
+        <p>Lead with a <a href="/wiki/Lead_link">link</a>.</p>
+        <div class="mw-heading mw-heading2"><h2 id="History">History</h2></div>
+        <p>Body with a <a href="/wiki/Body_link">link</a>.</p>
+    </div></div></body></html>"#;
+
+    const CAPTCHA_PAGE: &str = r#"<!DOCTYPE html><html><head><title>Just a moment...</title></head>
+        <body class="challenge"><form id="challenge-form" action="/verify" method="post">
+        <p>Please complete the security check to access <a href="/wiki/Main_Page">Wikipedia</a>.</p>
+        <input type="hidden" name="token" value="abc"></form></body></html>"#;
+
+    const JSON_BODY: &str =
+        r#"{"error":{"code":"maxlag","info":"Waiting for a database server: 5 seconds lagged."}}"#;
+
+    #[test]
+    fn non_html_content_types_are_rejected() {
+        assert_eq!(
+            unsupported_media_type(Some("application/json; charset=utf-8")),
+            Some("application/json".to_string())
+        );
+        assert_eq!(
+            unsupported_media_type(Some("Image/PNG")),
+            Some("image/png".to_string())
+        );
+        assert_eq!(
+            unsupported_media_type(Some("text/html; charset=UTF-8")),
+            None
+        );
+        assert_eq!(unsupported_media_type(Some("application/xhtml+xml")), None);
+        assert_eq!(unsupported_media_type(None), None);
+    }
+
+    #[test]
+    fn pages_without_the_article_skeleton_are_rejected() {
+        // A captcha interstitial is served as text/html, so only the skeleton check catches it
+        assert_eq!(unsupported_media_type(Some("text/html")), None);
+        assert!(!has_article_skeleton(&Html::parse_document(CAPTCHA_PAGE)));
+        assert!(!has_article_skeleton(&Html::parse_document(JSON_BODY)));
+        for page in [OLD_SKIN_PAGE, NEW_SKIN_PAGE, NO_SEE_ALSO_PAGE] {
+            assert!(has_article_skeleton(&Html::parse_document(page)));
+        }
+    }
+
+    fn scoped_links(page: &str, scope: LinkScope) -> Vec<String> {
+        let document = Html::parse_document(page);
+        let anchor = Selector::parse("a").unwrap();
+        scoped_roots(&document, scope)
+            .into_iter()
+            .flat_map(|root| root.select(&anchor))
+            .filter_map(|element| element.value().attr("href"))
+            .map(|href| href.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn see_also_scope_finds_the_section_in_both_skins() {
+        for page in [OLD_SKIN_PAGE, NEW_SKIN_PAGE] {
+            assert_eq!(
+                scoped_links(page, LinkScope::SeeAlso),
+                ["/wiki/Related_one", "/wiki/Related_two"]
+            );
+        }
+    }
+
+    #[test]
+    fn see_also_scope_is_empty_without_the_section() {
+        assert!(scoped_links(NO_SEE_ALSO_PAGE, LinkScope::SeeAlso).is_empty());
+    }
+
+    #[test]
+    fn lead_scope_stops_at_the_first_heading() {
+        for page in [OLD_SKIN_PAGE, NEW_SKIN_PAGE, NO_SEE_ALSO_PAGE] {
+            assert_eq!(scoped_links(page, LinkScope::Lead), ["/wiki/Lead_link"]);
+        }
+    }
+
+    #[test]
+    fn see_also_heading_matches_id_or_text() {
+        let heading = Selector::parse("h2, .mw-heading2").unwrap();
+        for (markup, expected) in [
+            (
+                r#"<h2><span class="mw-headline" id="See_also">See also</span></h2>"#,
+                true,
+            ),
+            (
+                r#"<div class="mw-heading mw-heading2"><h2 id="See_also">See also</h2></div>"#,
+                true,
+            ),
+            (r#"<h2 id="See_Also">Related</h2>"#, true),
+            ("<h2> See Also </h2>", true),
+            (
+                r#"<h2><span class="mw-headline" id="History">History</span></h2>"#,
+                false,
+            ),
+        ] {
+            let fragment = Html::parse_fragment(markup);
+            let element = fragment.select(&heading).next().unwrap();
+            assert!(is_section_heading(element), "{}", markup);
+            assert_eq!(is_see_also_heading(element), expected, "{}", markup);
+        }
+    }
+
+    #[test]
+    fn concurrent_claims_fetch_each_url_once() {
+        let claims = Arc::new(Claims::default());
+        let urls: Vec<String> = (0..200)
+            .map(|i| format!("https://en.wikipedia.org/wiki/Page_{}", i))
+            .collect();
+        let fetches = Arc::new(Mutex::new(HashMap::<String, usize>::new()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let claims = Arc::clone(&claims);
+                let fetches = Arc::clone(&fetches);
+                let urls = urls.clone();
+                thread::spawn(move || {
+                    // Every worker sees every URL several times, starting at a different offset
+                    for round in 0..5 {
+                        for i in 0..urls.len() {
+                            let url = &urls[(i + worker * 25 + round * 7) % urls.len()];
+                            if claims.claim(url, 2).is_some() {
+                                *fetches.lock().unwrap().entry(url.clone()).or_insert(0) += 1;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let fetches = fetches.lock().unwrap();
+        assert_eq!(fetches.len(), urls.len());
+        assert!(fetches.values().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn shallower_claims_win_and_released_claims_can_retry() {
+        let claims = Claims::default();
+        let url = "https://en.wikipedia.org/wiki/Graph_theory";
+
+        assert_eq!(claims.claim(url, 3), Some(None));
+        assert_eq!(claims.claim(url, 3), None);
+        assert_eq!(claims.claim(url, 4), None);
+        assert_eq!(claims.claim(url, 1), Some(Some(3)));
 
-    for handle in handles {
-        handle.join().unwrap();
+        // A failed fetch at depth 1 falls back to the depth 3 claim
+        claims.release(url, Some(3));
+        assert_eq!(claims.claim(url, 3), None);
+        assert_eq!(claims.claim(url, 2), Some(Some(3)));
+        claims.release(url, Some(3));
+        claims.release(url, None);
+        assert_eq!(claims.claim(url, 3), Some(None));
     }
 }
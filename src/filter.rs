@@ -0,0 +1,462 @@
+use crate::utils::{canonicalize_title, title_to_path, url_to_title};
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+const DEFAULT_NOISE_PAGES: &str = include_str!("noise_pages.txt");
+
+pub const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const DEFAULT_TITLE_PATTERNS: [&str; 5] = [
+    "List of ",
+    "Index of ",
+    "Outline of ",
+    "Glossary of ",
+    "Timeline of ",
+];
+
+pub const DEFAULT_EXCLUDED_NAMESPACES: [&str; 13] = [
+    "Special",
+    "File",
+    "Help",
+    "Wikipedia",
+    "User",
+    "Template",
+    "Category",
+    "Portal",
+    "Draft",
+    "Module",
+    "MediaWiki",
+    "TimedText",
+    "Appendix",
+];
+
+// How links to navigation-style articles (lists, indexes, outlines...) are handled
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TitlePatternMode {
+    Exclude,
+    NoExpand, // Recorded as boundary nodes but never fetched
+    Include,
+}
+
+impl FromStr for TitlePatternMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exclude" => Ok(TitlePatternMode::Exclude),
+            "no-expand" => Ok(TitlePatternMode::NoExpand),
+            "include" => Ok(TitlePatternMode::Include),
+            other => Err(format!(
+                "Unknown title pattern mode '{}' (expected exclude, no-expand or include)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LinkDecision {
+    Follow(String),
+    TitlePattern(TitlePatternMode, String),
+    Noise,
+    Reject(RejectReason),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RejectReason {
+    Invalid,           // Unparseable, not HTTP(S), or an empty title
+    OffDomain,         // Not an article path on any configured wiki
+    Query,             // Edit links, page histories and other non-article views
+    Namespace(String), // Talk pages, files, categories and the other excluded namespaces
+    SelfLink,          // Section anchors and links back to the page itself
+}
+
+impl RejectReason {
+    // Row label in the crawl summary's rejection breakdown
+    pub fn label(&self) -> &'static str {
+        match self {
+            RejectReason::Invalid => "invalid URL",
+            RejectReason::OffDomain => "off-domain",
+            RejectReason::Query => "query string",
+            RejectReason::Namespace(_) => "excluded namespace",
+            RejectReason::SelfLink => "link to the page itself",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DomainRule {
+    pub host: String,
+    pub path_prefix: String,
+    pub excluded_namespaces: Vec<String>,
+    pub capitalize_first_letter: bool,
+}
+
+impl DomainRule {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            path_prefix: "/wiki/".to_string(),
+            excluded_namespaces: DEFAULT_EXCLUDED_NAMESPACES
+                .iter()
+                .map(|namespace| namespace.to_string())
+                .collect(),
+            capitalize_first_letter: true,
+        }
+    }
+
+    fn excluded_namespace<'t>(&self, title: &'t str) -> Option<&'t str> {
+        let (namespace, _) = title.split_once(':')?;
+        let excluded = namespace == "Talk"
+            || namespace.ends_with(" talk")
+            || self.excluded_namespaces.iter().any(|ns| ns == namespace);
+        excluded.then_some(namespace)
+    }
+}
+
+#[derive(Serialize)]
+pub struct URLFilter {
+    domains: Vec<DomainRule>,
+    title_patterns: Vec<String>,
+    title_pattern_mode: TitlePatternMode,
+    #[serde(skip)]
+    noise_pages: HashSet<String>,
+    date_titles_are_noise: bool,
+}
+
+impl URLFilter {
+    pub fn new(
+        domains: Vec<DomainRule>,
+        title_pattern_mode: TitlePatternMode,
+        noise_pages: HashSet<String>,
+        date_titles_are_noise: bool,
+    ) -> Self {
+        Self {
+            domains,
+            title_patterns: DEFAULT_TITLE_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            title_pattern_mode,
+            noise_pages,
+            date_titles_are_noise,
+        }
+    }
+
+    pub fn classify_url(&self, url: &str) -> LinkDecision {
+        match Url::parse(url) {
+            Ok(parsed) => self.classify_resolved(parsed, None),
+            Err(_) => LinkDecision::Reject(RejectReason::Invalid),
+        }
+    }
+
+    pub fn page_title(&self, url: &Url) -> Option<String> {
+        self.match_title(url).map(|(_, title)| title)
+    }
+
+    // Classifies a bare title (as returned by the MediaWiki API) as a link on the page's wiki
+    pub fn classify_title(&self, page_url: &Url, title: &str) -> LinkDecision {
+        match self.rule_for(page_url) {
+            Some(rule) => {
+                let href = format!("{}{}", rule.path_prefix, title_to_path(title));
+                self.classify(page_url, &href)
+            }
+            None => LinkDecision::Reject(RejectReason::OffDomain),
+        }
+    }
+
+    fn rule_for(&self, url: &Url) -> Option<&DomainRule> {
+        url.host_str()
+            .and_then(|host| self.domains.iter().find(|rule| rule.host == host))
+    }
+
+    fn match_title(&self, url: &Url) -> Option<(&DomainRule, String)> {
+        let rule = self.rule_for(url)?;
+        let path = url.path().strip_prefix(rule.path_prefix.as_str())?;
+        Some((
+            rule,
+            canonicalize_title(&url_to_title(path), rule.capitalize_first_letter),
+        ))
+    }
+
+    // Resolves href against the page it was found on, so links keep the page's own domain
+    pub fn classify(&self, page_url: &Url, href: &str) -> LinkDecision {
+        // Fragment-only hrefs (footnotes, section anchors) point back into the same page
+        if href.starts_with('#') {
+            return LinkDecision::Reject(RejectReason::SelfLink);
+        }
+        match page_url.join(href) {
+            Ok(url) => self.classify_resolved(url, Some(page_url)),
+            Err(_) => LinkDecision::Reject(RejectReason::Invalid),
+        }
+    }
+
+    // Links from `page_url` back to the page itself, under any spelling of its title, are rejected
+    fn classify_resolved(&self, mut url: Url, page_url: Option<&Url>) -> LinkDecision {
+        if !matches!(url.scheme(), "http" | "https") {
+            return LinkDecision::Reject(RejectReason::Invalid);
+        }
+        if url.query().is_some() {
+            return LinkDecision::Reject(RejectReason::Query);
+        }
+
+        let (rule, title) = match self.match_title(&url) {
+            Some(matched) => matched,
+            None => return LinkDecision::Reject(RejectReason::OffDomain),
+        };
+        if title.is_empty() {
+            return LinkDecision::Reject(RejectReason::Invalid);
+        }
+        if let Some(namespace) = rule.excluded_namespace(&title) {
+            return LinkDecision::Reject(RejectReason::Namespace(namespace.to_string()));
+        }
+        if page_url.is_some_and(|page_url| {
+            page_url.host_str() == url.host_str()
+                && self.page_title(page_url).as_deref() == Some(title.as_str())
+        }) {
+            return LinkDecision::Reject(RejectReason::SelfLink);
+        }
+
+        if self.noise_pages.contains(&title)
+            || (self.date_titles_are_noise && is_date_title(&title))
+        {
+            return LinkDecision::Noise;
+        }
+
+        // Rebuild the path from the canonical title so variants of a title map to one URL
+        url.set_path(&format!("{}{}", rule.path_prefix, title_to_path(&title)));
+        url.set_fragment(None);
+        if url.scheme() == "http" && url.set_scheme("https").is_err() {
+            return LinkDecision::Reject(RejectReason::Invalid);
+        }
+
+        if self
+            .title_patterns
+            .iter()
+            .any(|pattern| title.starts_with(pattern.as_str()))
+        {
+            LinkDecision::TitlePattern(self.title_pattern_mode, url.to_string())
+        } else {
+            LinkDecision::Follow(url.to_string())
+        }
+    }
+}
+
+// The embedded default list unless `include_defaults` is off, plus the titles from `extra_file`
+pub fn load_noise_pages(
+    extra_file: Option<&str>,
+    include_defaults: bool,
+) -> io::Result<HashSet<String>> {
+    let mut noise_pages = if include_defaults {
+        parse_noise_pages(DEFAULT_NOISE_PAGES)
+    } else {
+        HashSet::new()
+    };
+    if let Some(path) = extra_file {
+        noise_pages.extend(parse_noise_pages(&fs::read_to_string(path)?));
+    }
+    Ok(noise_pages)
+}
+
+fn parse_noise_pages(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        // Compared against canonical titles, so "isbn" and "Digital_object  identifier" match too
+        .map(|line| canonicalize_title(line, true))
+        .collect()
+}
+
+// Years ("1999", "1990s", "AD 800", "300 BC") and calendar days ("March 5")
+fn is_date_title(title: &str) -> bool {
+    let year = title
+        .trim_start_matches("AD ")
+        .trim_end_matches(" BC")
+        .trim_end_matches(" BCE")
+        .trim_end_matches('s');
+    if !year.is_empty() && year.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+
+    match title.split_once(' ') {
+        Some((month, day)) => {
+            MONTHS.contains(&month)
+                && !day.is_empty()
+                && day.len() <= 2
+                && day.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn filter_with(noise_pages: HashSet<String>, date_titles_are_noise: bool) -> URLFilter {
+        URLFilter::new(
+            vec![DomainRule::new("en.wikipedia.org")],
+            TitlePatternMode::Exclude,
+            noise_pages,
+            date_titles_are_noise,
+        )
+    }
+
+    fn filter() -> URLFilter {
+        filter_with(HashSet::new(), true)
+    }
+
+    #[test]
+    fn links_back_to_the_page_are_rejected() {
+        let filter = filter();
+        let page_url =
+            Url::parse("https://en.wikipedia.org/wiki/Rust_(programming_language)").unwrap();
+
+        for href in [
+            "#History",
+            "#cite_note-1",
+            "/wiki/Rust_(programming_language)",
+            "/wiki/Rust_(programming_language)#Syntax",
+            "/wiki/rust_(programming_language)",
+            "https://en.wikipedia.org/wiki/Rust%20(programming%20language)",
+        ] {
+            assert_eq!(
+                filter.classify(&page_url, href),
+                LinkDecision::Reject(RejectReason::SelfLink),
+                "{}",
+                href
+            );
+        }
+        assert_eq!(
+            filter.classify(&page_url, "/wiki/Cargo_(package_manager)#Usage"),
+            LinkDecision::Follow(
+                "https://en.wikipedia.org/wiki/Cargo_(package_manager)".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn classify_url_accepts_the_url_itself() {
+        assert_eq!(
+            filter().classify_url("https://en.wikipedia.org/wiki/Graph_theory"),
+            LinkDecision::Follow("https://en.wikipedia.org/wiki/Graph_theory".to_string())
+        );
+    }
+
+    #[test]
+    fn noise_file_entries_are_canonicalized() {
+        let dir = temp_dir("noise-file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("noise.txt");
+        fs::write(&path, "# comment\nisbn\nDigital  object_identifier\n\n").unwrap();
+        let noise_pages = load_noise_pages(path.to_str(), false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let filter = filter_with(noise_pages, false);
+        for url in [
+            "https://en.wikipedia.org/wiki/Isbn",
+            "https://en.wikipedia.org/wiki/Digital_object_identifier",
+        ] {
+            assert_eq!(filter.classify_url(url), LinkDecision::Noise);
+        }
+    }
+
+    #[test]
+    fn default_noise_can_be_turned_off() {
+        let noise_pages = load_noise_pages(None, false).unwrap();
+        assert!(noise_pages.is_empty());
+
+        let with_defaults = filter_with(HashSet::new(), true);
+        let without_defaults = filter_with(noise_pages, false);
+        for url in [
+            "https://en.wikipedia.org/wiki/1999",
+            "https://en.wikipedia.org/wiki/March_5",
+        ] {
+            assert_eq!(with_defaults.classify_url(url), LinkDecision::Noise);
+            assert_eq!(
+                without_defaults.classify_url(url),
+                LinkDecision::Follow(url.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn title_patterns_follow_the_configured_mode() {
+        let page_url = Url::parse("https://en.wikipedia.org/wiki/Graph_theory").unwrap();
+        let list = "https://en.wikipedia.org/wiki/List_of_graph_theory_topics".to_string();
+        for mode in [
+            TitlePatternMode::Exclude,
+            TitlePatternMode::NoExpand,
+            TitlePatternMode::Include,
+        ] {
+            let filter = URLFilter::new(
+                vec![DomainRule::new("en.wikipedia.org")],
+                mode,
+                HashSet::new(),
+                true,
+            );
+            assert_eq!(
+                filter.classify(&page_url, "/wiki/List_of_graph_theory_topics"),
+                LinkDecision::TitlePattern(mode, list.clone())
+            );
+            assert_eq!(
+                filter.classify(&page_url, "/wiki/Planar_graph"),
+                LinkDecision::Follow("https://en.wikipedia.org/wiki/Planar_graph".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejections_carry_their_reason() {
+        let filter = filter();
+        let page_url = Url::parse("https://en.wikipedia.org/wiki/Graph_theory").unwrap();
+        for (href, reason) in [
+            (
+                "/wiki/Talk:Graph_theory",
+                RejectReason::Namespace("Talk".to_string()),
+            ),
+            (
+                "/wiki/File:Petersen_graph.svg",
+                RejectReason::Namespace("File".to_string()),
+            ),
+            (
+                "/w/index.php?title=Graph_theory&action=edit",
+                RejectReason::Query,
+            ),
+            (
+                "https://de.wikipedia.org/wiki/Graphentheorie",
+                RejectReason::OffDomain,
+            ),
+            ("/w/index.php", RejectReason::OffDomain),
+            ("mailto:someone@example.org", RejectReason::Invalid),
+            ("#References", RejectReason::SelfLink),
+        ] {
+            assert_eq!(
+                filter.classify(&page_url, href),
+                LinkDecision::Reject(reason),
+                "{}",
+                href
+            );
+        }
+    }
+}
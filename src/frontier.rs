@@ -0,0 +1,247 @@
+use crossbeam::queue::SegQueue;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+type Entry = (String, usize); // (URL, depth)
+
+// A frontier chunk spilled to disk, one JSON entry per line
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Segment {
+    pub file: String, // File name inside the frontier directory
+    pub entries: usize,
+    // Added to each entry's depth when read back, for segments written under another depth limit
+    #[serde(default)]
+    pub depth_shift: isize,
+}
+
+// The crawl queue. Up to `memory_limit` entries live in memory, the rest spill to segment
+// files of `segment_size` entries that are read back as workers drain the queue
+pub struct FrontierStore {
+    memory: SegQueue<Entry>,
+    memory_limit: usize,
+    segment_size: usize,
+    dir: PathBuf,
+    spill: Mutex<Spill>,
+}
+
+#[derive(Default)]
+struct Spill {
+    buffer: Vec<Entry>,          // Spilled entries not yet written out
+    segments: VecDeque<Segment>, // Oldest first
+    consumed: Vec<PathBuf>,      // Read back, deleted once a checkpoint no longer needs them
+    next_index: usize,
+}
+
+impl FrontierStore {
+    pub fn new(dir: PathBuf, memory_limit: usize, segment_size: usize) -> Self {
+        Self {
+            memory: SegQueue::new(),
+            memory_limit,
+            segment_size,
+            dir,
+            spill: Mutex::new(Spill::default()),
+        }
+    }
+
+    // Queues segments recorded by a previous checkpoint; they are only read once needed
+    pub fn restore_segments(&self, segments: Vec<Segment>) {
+        self.spill.lock().unwrap().segments.extend(segments);
+    }
+
+    pub fn push(&self, entry: Entry) {
+        if self.memory.len() < self.memory_limit {
+            self.memory.push(entry);
+            return;
+        }
+
+        let mut spill = self.spill.lock().unwrap();
+        spill.buffer.push(entry);
+        if spill.buffer.len() >= self.segment_size {
+            self.write_buffer(&mut spill)
+                .expect("Failed to write frontier segment");
+        }
+    }
+
+    pub fn pop(&self) -> Option<Entry> {
+        if let Some(entry) = self.memory.pop() {
+            return Some(entry);
+        }
+
+        let mut spill = self.spill.lock().unwrap();
+        // Another worker may have refilled memory while this one waited for the lock
+        if let Some(entry) = self.memory.pop() {
+            return Some(entry);
+        }
+        if let Some(segment) = spill.segments.pop_front() {
+            let path = self.dir.join(&segment.file);
+            for (url, depth) in read_segment(&path).expect("Failed to read frontier segment") {
+                self.memory
+                    .push((url, depth.saturating_add_signed(segment.depth_shift)));
+            }
+            spill.consumed.push(path);
+        } else {
+            for entry in spill.buffer.drain(..) {
+                self.memory.push(entry);
+            }
+        }
+        self.memory.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        let spill = self.spill.lock().unwrap();
+        let on_disk: usize = spill.segments.iter().map(|segment| segment.entries).sum();
+        self.memory.len() + spill.buffer.len() + on_disk
+    }
+
+    // Drains the in-memory entries for the state file and writes any spill buffer out, so the
+    // checkpoint only has to list the segments
+    pub fn checkpoint(&self) -> io::Result<(Vec<Entry>, Vec<Segment>)> {
+        let mut inline = Vec::new();
+        while let Some(entry) = self.memory.pop() {
+            inline.push(entry);
+        }
+
+        let mut spill = self.spill.lock().unwrap();
+        if !spill.buffer.is_empty() {
+            self.write_buffer(&mut spill)?;
+        }
+        Ok((inline, spill.segments.iter().cloned().collect()))
+    }
+
+    // Call after the checkpoint referencing the remaining segments has been saved
+    pub fn remove_consumed(&self) -> io::Result<()> {
+        let mut spill = self.spill.lock().unwrap();
+        for path in spill.consumed.drain(..) {
+            match fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn write_buffer(&self, spill: &mut Spill) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Segments listed by an older checkpoint may still be on disk, never overwrite them
+        let file = loop {
+            spill.next_index += 1;
+            let file = format!("segment-{:06}.jsonl", spill.next_index);
+            if !self.dir.join(&file).exists() {
+                break file;
+            }
+        };
+
+        write_segment(&self.dir.join(&file), &spill.buffer)?;
+        spill.segments.push_back(Segment {
+            file,
+            entries: spill.buffer.len(),
+            depth_shift: 0,
+        });
+        spill.buffer.clear();
+        Ok(())
+    }
+}
+
+// Rewrites a segment in `dir` with only the entries `keep` accepts. Returns how many were dropped.
+pub fn retain_segment(
+    dir: &Path,
+    segment: &mut Segment,
+    mut keep: impl FnMut(&Entry) -> bool,
+) -> io::Result<usize> {
+    let path = dir.join(&segment.file);
+    let mut entries = read_segment(&path)?;
+    let before = entries.len();
+    entries.retain(|entry| keep(entry));
+    if entries.len() < before {
+        // Written aside and renamed so a crash mid-rewrite cannot truncate the segment
+        let rewritten = path.with_extension("jsonl.tmp");
+        write_segment(&rewritten, &entries)?;
+        fs::rename(&rewritten, &path)?;
+    }
+    segment.entries = entries.len();
+    Ok(before - entries.len())
+}
+
+fn write_segment(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+fn read_segment(path: &Path) -> io::Result<Vec<Entry>> {
+    let reader = BufReader::new(File::open(path)?);
+    reader
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::collections::HashSet;
+
+    #[test]
+    fn checkpoint_and_resume_keeps_every_entry_once() {
+        let dir = temp_dir("frontier-resume");
+        let entries: Vec<Entry> = (0..1000)
+            .map(|i| (format!("https://en.wikipedia.org/wiki/Page_{}", i), i % 4))
+            .collect();
+
+        let store = FrontierStore::new(dir.clone(), 10, 50);
+        for entry in &entries {
+            store.push(entry.clone());
+        }
+        assert_eq!(store.len(), entries.len());
+
+        // Drain past memory and into the spilled segments before checkpointing
+        let mut seen = Vec::new();
+        for _ in 0..300 {
+            seen.push(store.pop().unwrap());
+        }
+        let (inline, segments) = store.checkpoint().unwrap();
+        store.remove_consumed().unwrap();
+        assert!(!segments.is_empty());
+        assert!(segments.iter().all(|segment| !segment.file.contains('/')));
+
+        let resumed = FrontierStore::new(dir.clone(), 10, 50);
+        for entry in inline {
+            resumed.push(entry);
+        }
+        resumed.restore_segments(segments);
+        assert_eq!(resumed.len(), entries.len() - seen.len());
+        while let Some(entry) = resumed.pop() {
+            seen.push(entry);
+        }
+
+        assert_eq!(seen.len(), entries.len());
+        let unique: HashSet<&Entry> = seen.iter().collect();
+        assert_eq!(unique, entries.iter().collect());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retain_segment_drops_rejected_entries() {
+        let dir = temp_dir("frontier-retain");
+        let store = FrontierStore::new(dir.clone(), 0, 4);
+        for i in 0..4 {
+            store.push((format!("https://en.wikipedia.org/wiki/Page_{}", i), 0));
+        }
+        let (_, mut segments) = store.checkpoint().unwrap();
+
+        let pruned =
+            retain_segment(&dir, &mut segments[0], |(url, _)| !url.ends_with('2')).unwrap();
+        assert_eq!(pruned, 1);
+        assert_eq!(segments[0].entries, 3);
+        assert_eq!(read_segment(&dir.join(&segments[0].file)).unwrap().len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,51 +1,227 @@
+mod api_links;
+mod circuit_breaker;
+mod config;
 mod crawler;
+mod filter;
+mod frontier;
+mod manifest;
+mod output;
+mod project;
+mod rate_limit;
+mod robots;
 mod state;
 mod stats;
+mod summary;
+#[cfg(test)]
+mod test_util;
+mod timing;
+mod trap;
 mod utils;
 
 use crate::crawler::start_crawl;
-use crossbeam::queue::SegQueue;
-use state::{load_state, save_state};
+use config::CrawlerConfig;
+use filter::{load_noise_pages, LinkDecision, URLFilter};
+use frontier::FrontierStore;
+use manifest::{load_manifest, manifest_path, save_manifest, Manifest};
+use output::output_path;
+use state::{latest_state_path, load_state, save_state, FRONTIER_DIR, STATE_FILE, VISITED_FILE};
 use stats::CrawlStats;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use summary::{save_summary, CrawlSummary, SUMMARY_FILE};
 
 fn main() {
-    let start_url = "https://en.wikipedia.org/wiki/Rust_(programming_language)";
-    let queue = Arc::new(SegQueue::new());
+    let config = Arc::new(CrawlerConfig::from_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }));
+    if let Err(err) = config.validate_identity() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    // Resolved up front so an existing file in error mode fails before the crawl, not after it
+    let now = stats::current_time_millis();
+    let resolve = |file_name: &str, mode| {
+        output_path(&config.out_dir, file_name, mode, now).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    };
+    let visited_path = resolve(VISITED_FILE, config.export_overwrite);
+    let state_path = resolve(STATE_FILE, config.state_overwrite);
+    let summary_path = config
+        .write_summary
+        .then(|| resolve(SUMMARY_FILE, config.export_overwrite));
+    let export_visited_path = config
+        .export_visited
+        .as_deref()
+        .map(|file_name| resolve(file_name, config.export_overwrite));
+    // Before anything is written this run, so a fresh state file is never picked up
+    let previous_state_path = latest_state_path(&config.out_dir);
+
+    let queue = Arc::new(FrontierStore::new(
+        config.out_dir.join(FRONTIER_DIR),
+        config.frontier_memory_limit,
+        config.frontier_segment_size,
+    ));
     let visited = Arc::new(Mutex::new(Vec::<String>::new()));
+    let depths = Arc::new(Mutex::new(HashMap::<String, usize>::new()));
+    let fetched = Arc::new(Mutex::new(HashSet::<String>::new()));
     let stats = Arc::new(Mutex::new(CrawlStats::new()));
+    let noise_pages = load_noise_pages(config.noise_pages_file.as_deref(), config.default_noise)
+        .unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to load noise pages file {}: {}",
+                config.noise_pages_file.as_deref().unwrap_or_default(),
+                err
+            );
+            std::process::exit(1);
+        });
+    let filter = Arc::new(URLFilter::new(
+        config
+            .domains
+            .iter()
+            .map(|host| project::domain_rule(host))
+            .collect(),
+        config.title_pattern_mode,
+        noise_pages,
+        config.default_noise,
+    ));
+
+    let seeds: Vec<(String, usize)> = config
+        .seed_start_depths()
+        .into_iter()
+        .map(|(url, depth)| {
+            let url = match filter.classify_url(&url) {
+                LinkDecision::Follow(canonical) | LinkDecision::TitlePattern(_, canonical) => {
+                    canonical
+                }
+                _ => url,
+            };
+            (url, depth)
+        })
+        .collect();
+
+    let mut visited_pages = Vec::new();
+    let mut known_depths = HashMap::new();
 
     // Load crawl state if available
-    if let Ok(state) = load_state() {
+    if let Ok(mut state) = load_state(&previous_state_path) {
+        if let Ok(manifest) = load_manifest(&previous_state_path) {
+            println!("Resuming {}", manifest.summary());
+        }
+        if config.reconcile {
+            let (pruned_visited, pruned_queued) = state
+                .reconcile(&filter, &config.out_dir.join(FRONTIER_DIR))
+                .expect("Failed to reconcile frontier segments");
+            println!(
+                "Reconciled crawl state with the current filter: pruned {} visited and {} queued URLs",
+                pruned_visited, pruned_queued
+            );
+        }
+        state.rebase_depths(config.depth_limit());
         for (url, depth) in state.queue {
             queue.push((url, depth));
         }
-        let mut visited_guard = visited.lock().unwrap();
-        *visited_guard = state.visited;
-    } else {
-        queue.push((start_url.to_string(), 0));
+        queue.restore_segments(state.queue_segments);
+        visited_pages = state.visited;
+        known_depths = state.depths;
+        *fetched.lock().unwrap() = state.fetched.into_iter().collect();
+    }
+
+    // Seed pages that are new, or were only reached deeper than their seed depth before
+    for (url, depth) in seeds {
+        let known = visited_pages.contains(&url);
+        let reached_deeper = known_depths
+            .get(&url)
+            .is_some_and(|&known_depth| known_depth > depth);
+        if known && !reached_deeper {
+            continue;
+        }
+        queue.push((url.clone(), depth));
+        known_depths.insert(url.clone(), depth);
+        if !known {
+            visited_pages.push(url);
+        }
     }
+    *visited.lock().unwrap() = visited_pages;
+    *depths.lock().unwrap() = known_depths;
 
-    start_crawl(&queue, &visited, &stats);
+    let outcome = start_crawl(
+        &queue, &visited, &depths, &fetched, &stats, &filter, &config,
+    );
+    println!("Crawl finished: {}", outcome);
 
     let visited_pages = visited.lock().unwrap();
     println!("Visited pages: {:?}", *visited_pages);
-    state::save_visited(&visited_pages).expect("Failed to save visited pages");
+    state::save_visited(&visited_pages, &visited_path).expect("Failed to save visited pages");
+
+    let stats_guard = stats.lock().unwrap();
+    let mut manifest = Manifest::new(&config, &filter, stats_guard.start_time);
+    manifest.outcome = outcome.to_string();
+    manifest.max_relative_depth = depths.lock().unwrap().values().copied().max().unwrap_or(0);
+    manifest.pages_known = visited_pages.len();
+    manifest.pages_fetched = fetched.lock().unwrap().len();
+    manifest.links_followed = stats_guard.links_followed;
 
     // Save crawl state
+    let (inline_queue, queue_segments) =
+        queue.checkpoint().expect("Failed to checkpoint the queue");
     let state = state::CrawlState {
-        queue: {
-            let mut queue_vec = vec![];
-            while let Some(item) = queue.pop() {
-                queue_vec.push(item);
-            }
-            queue_vec
-        },
+        queue: inline_queue,
+        queue_segments,
         visited: visited_pages.clone(),
+        depths: depths.lock().unwrap().clone(),
+        fetched: fetched.lock().unwrap().iter().cloned().collect(),
+        user_agent: Some(config.user_agent()),
+        depth_limit: Some(config.depth_limit()),
     };
-    save_state(&state).expect("Failed to save crawl state");
+    save_state(&state, &state_path).expect("Failed to save crawl state");
+    queue
+        .remove_consumed()
+        .expect("Failed to remove consumed queue segments");
+    let queued = state.queue.len()
+        + state
+            .queue_segments
+            .iter()
+            .map(|segment| segment.entries)
+            .sum::<usize>();
+
+    manifest.queued = queued;
+    for export_path in [&visited_path, &state_path] {
+        save_manifest(&manifest, export_path).expect("Failed to save crawl manifest");
+    }
+
+    let summary = CrawlSummary::new(
+        outcome.to_string(),
+        &stats_guard,
+        &state.depths,
+        config.depth_limit(),
+        visited_pages.len(),
+        fetched.lock().unwrap().len(),
+        queued,
+    );
+    if let Some(export_path) = &export_visited_path {
+        state::export_visited(&fetched.lock().unwrap(), export_path)
+            .expect("Failed to export visited pages");
+    }
+    if let Some(summary_path) = &summary_path {
+        save_summary(&summary, summary_path).expect("Failed to save crawl summary");
+    }
+
+    println!("Wrote:");
+    for export_path in [&visited_path, &state_path] {
+        println!("  {}", export_path.display());
+        println!("  {}", manifest_path(export_path).display());
+    }
+    if let Some(export_path) = &export_visited_path {
+        println!("  {}", export_path.display());
+    }
+    if let Some(summary_path) = &summary_path {
+        println!("  {}", summary_path.display());
+    }
 
     // Show statistics
-    let stats_guard = stats.lock().unwrap();
-    println!("Crawl statistics: {:?}", *stats_guard);
+    print!("{}", summary);
+    timing::print_timing_table(&stats_guard.timings);
 }
@@ -0,0 +1,163 @@
+use crate::config::CrawlerConfig;
+use crate::filter::URLFilter;
+use crate::stats::current_time_millis;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Records what produced an export, written next to it as <basename>.manifest.json
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub crate_version: String,
+    pub started_at: u64,             // Time in milliseconds since UNIX_EPOCH
+    pub finished_at: u64,            // Time in milliseconds since UNIX_EPOCH
+    pub seeds: Vec<(String, usize)>, // (URL, max depth)
+    #[serde(default)]
+    pub user_agent: String, // As sent, including the contact appended to the default
+    pub config: Value,
+    pub filter: Value,
+    pub outcome: String,
+    // Budget-relative like every stored depth: depth_limit minus the smallest remaining budget
+    #[serde(alias = "max_depth_reached")]
+    pub max_relative_depth: usize,
+    #[serde(default)]
+    pub depth_limit: usize,
+    pub pages_known: usize, // Every URL recorded, fetched or not, across all sessions
+    pub pages_fetched: usize, // Across all sessions, like pages_known
+    pub links_followed: usize,
+    pub queued: usize,
+}
+
+impl Manifest {
+    pub fn new(config: &CrawlerConfig, filter: &URLFilter, started_at: u64) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at,
+            finished_at: current_time_millis(),
+            seeds: config.seed_max_depths(),
+            user_agent: config.user_agent(),
+            config: serde_json::to_value(config).unwrap_or_default(),
+            filter: serde_json::to_value(filter).unwrap_or_default(),
+            outcome: String::new(),
+            max_relative_depth: 0,
+            depth_limit: config.depth_limit(),
+            pages_known: 0,
+            pages_fetched: 0,
+            links_followed: 0,
+            queued: 0,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let age_secs = current_time_millis().saturating_sub(self.finished_at) / 1000;
+        format!(
+            "crawl from {} ago (wikipedia-mapper {}, depth {} of {}, {} pages fetched, {} known, {})",
+            format_age(age_secs),
+            self.crate_version,
+            self.max_relative_depth,
+            self.depth_limit,
+            self.pages_fetched,
+            self.pages_known,
+            self.outcome
+        )
+    }
+}
+
+pub fn manifest_path(export_path: &Path) -> PathBuf {
+    let stem = export_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    export_path.with_file_name(format!("{}.manifest.json", stem))
+}
+
+pub fn save_manifest(manifest: &Manifest, export_path: &Path) -> io::Result<()> {
+    let serialized = serde_json::to_string_pretty(manifest)?;
+    let mut file = File::create(manifest_path(export_path))?;
+    file.write_all(serialized.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_manifest(export_path: &Path) -> io::Result<Manifest> {
+    let file = File::open(manifest_path(export_path))?;
+    let manifest: Manifest = serde_json::from_reader(file)?;
+    Ok(manifest)
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=119 => format!("{} seconds", secs),
+        120..=7199 => format!("{} minutes", secs / 60),
+        7200..=172_799 => format!("{} hours", secs / 3600),
+        _ => format!("{} days", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{DomainRule, TitlePatternMode};
+    use crate::test_util::temp_dir;
+    use std::collections::HashSet;
+    use std::fs;
+
+    #[test]
+    fn manifest_records_the_config_used() {
+        let config = CrawlerConfig {
+            seeds: vec![
+                (
+                    "https://en.wikipedia.org/wiki/Graph_theory".to_string(),
+                    None,
+                ),
+                (
+                    "https://en.wikipedia.org/wiki/Topology".to_string(),
+                    Some(1),
+                ),
+            ],
+            max_depth: Some(4),
+            contact: Some("mapper@wikipedia-mapper.dev".to_string()),
+            ..CrawlerConfig::default()
+        };
+        let filter = URLFilter::new(
+            vec![DomainRule::new("en.wikipedia.org")],
+            TitlePatternMode::NoExpand,
+            HashSet::new(),
+            true,
+        );
+
+        let manifest = Manifest::new(&config, &filter, 1_700_000_000_000);
+        assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest.started_at, 1_700_000_000_000);
+        assert_eq!(
+            manifest.seeds,
+            [
+                ("https://en.wikipedia.org/wiki/Graph_theory".to_string(), 4),
+                ("https://en.wikipedia.org/wiki/Topology".to_string(), 1),
+            ]
+        );
+        assert_eq!(manifest.user_agent, config.user_agent());
+        assert_eq!(manifest.config, serde_json::to_value(&config).unwrap());
+        assert_eq!(manifest.config["max_depth"], 4);
+        assert_eq!(manifest.filter, serde_json::to_value(&filter).unwrap());
+        assert_eq!(manifest.filter["title_pattern_mode"], "no-expand");
+    }
+
+    #[test]
+    fn manifest_is_written_next_to_the_export() {
+        let dir = temp_dir("manifest");
+        fs::create_dir_all(&dir).unwrap();
+        let export_path = dir.join("visited_pages.json");
+        let filter = URLFilter::new(Vec::new(), TitlePatternMode::Exclude, HashSet::new(), true);
+        let mut manifest = Manifest::new(&CrawlerConfig::default(), &filter, 0);
+        manifest.pages_fetched = 12;
+
+        save_manifest(&manifest, &export_path).unwrap();
+        assert!(dir.join("visited_pages.manifest.json").is_file());
+        let loaded = load_manifest(&export_path).unwrap();
+        assert_eq!(loaded.pages_fetched, 12);
+        assert_eq!(loaded.config, manifest.config);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// What to do when an output file already exists
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwriteMode {
+    Overwrite,
+    ErrorIfExists,
+    // Write next to it as <stem>.<YYYY-MM-DDTHH-MM>.<ext>
+    TimestampSuffix,
+}
+
+impl FromStr for OverwriteMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(OverwriteMode::Overwrite),
+            "error" => Ok(OverwriteMode::ErrorIfExists),
+            "timestamp" => Ok(OverwriteMode::TimestampSuffix),
+            other => Err(format!(
+                "Unknown overwrite mode '{}' (expected overwrite, error or timestamp)",
+                other
+            )),
+        }
+    }
+}
+
+// Picks where `file_name` should be written inside `out_dir`, creating the directory if needed
+pub fn output_path(
+    out_dir: &Path,
+    file_name: &str,
+    mode: OverwriteMode,
+    now_millis: u64,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(out_dir)?;
+    let path = out_dir.join(file_name);
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    match mode {
+        OverwriteMode::Overwrite => Ok(path),
+        OverwriteMode::ErrorIfExists => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists (pass a different overwrite mode or --out-dir)",
+                path.display()
+            ),
+        )),
+        OverwriteMode::TimestampSuffix => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path
+                .extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_default();
+            let timestamp = format_timestamp(now_millis);
+            // Two runs within the same minute get a counter rather than clobbering each other
+            let candidates = std::iter::once(format!("{}.{}{}", stem, timestamp, extension))
+                .chain((2..).map(|n| format!("{}.{}-{}{}", stem, timestamp, n, extension)));
+            Ok(candidates
+                .map(|name| out_dir.join(name))
+                .find(|candidate| !candidate.exists())
+                .unwrap())
+        }
+    }
+}
+
+// UTC time as YYYY-MM-DDTHH-MM, with dashes so it is safe in file names everywhere
+fn format_timestamp(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let minutes_of_day = (secs % 86_400) / 60;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}",
+        year,
+        month,
+        day,
+        minutes_of_day / 60,
+        minutes_of_day % 60
+    )
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    const NOW: u64 = 1_700_000_000_000; // 2023-11-14 22:13:20 UTC
+
+    #[test]
+    fn missing_file_is_written_in_place_in_every_mode() {
+        let dir = temp_dir("output-missing").join("nested");
+        for mode in [
+            OverwriteMode::Overwrite,
+            OverwriteMode::ErrorIfExists,
+            OverwriteMode::TimestampSuffix,
+        ] {
+            let path = output_path(&dir, "visited_pages.json", mode, NOW).unwrap();
+            assert_eq!(path, dir.join("visited_pages.json"));
+        }
+        assert!(dir.is_dir());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn existing_file_is_overwritten() {
+        let dir = temp_dir("output-overwrite");
+        let existing =
+            output_path(&dir, "crawl_state.json", OverwriteMode::Overwrite, NOW).unwrap();
+        fs::write(&existing, "{}").unwrap();
+        let path = output_path(&dir, "crawl_state.json", OverwriteMode::Overwrite, NOW).unwrap();
+        assert_eq!(path, existing);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_file_is_an_error() {
+        let dir = temp_dir("output-error");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("summary.json"), "{}").unwrap();
+        let err = output_path(&dir, "summary.json", OverwriteMode::ErrorIfExists, NOW).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_file_gets_a_timestamped_sibling() {
+        let dir = temp_dir("output-timestamp");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("visited_pages.json"), "[]").unwrap();
+
+        let first = output_path(
+            &dir,
+            "visited_pages.json",
+            OverwriteMode::TimestampSuffix,
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(first, dir.join("visited_pages.2023-11-14T22-13.json"));
+        fs::write(&first, "[]").unwrap();
+        let second = output_path(
+            &dir,
+            "visited_pages.json",
+            OverwriteMode::TimestampSuffix,
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(second, dir.join("visited_pages.2023-11-14T22-13-2.json"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format_timestamp_is_utc_minutes() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00-00");
+        assert_eq!(format_timestamp(NOW), "2023-11-14T22-13");
+        assert_eq!(format_timestamp(951_827_400_000), "2000-02-29T12-30");
+        assert_eq!(format_timestamp(4_107_542_399_000), "2100-02-28T23-59");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::filter::{DomainRule, DEFAULT_EXCLUDED_NAMESPACES};
+
+pub struct ProjectPreset {
+    pub name: &'static str,
+    pub host: &'static str,
+    pub path_prefix: &'static str,
+    pub excluded_namespaces: &'static [&'static str],
+    pub start_page: &'static str,
+    pub capitalize_first_letter: bool,
+}
+
+pub const PROJECTS: [ProjectPreset; 7] = [
+    ProjectPreset {
+        name: "wikipedia",
+        host: "en.wikipedia.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &DEFAULT_EXCLUDED_NAMESPACES,
+        start_page: "Rust_(programming_language)",
+        capitalize_first_letter: true,
+    },
+    ProjectPreset {
+        name: "wiktionary",
+        host: "en.wiktionary.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &[
+            "Special",
+            "File",
+            "Help",
+            "Wiktionary",
+            "User",
+            "Template",
+            "Category",
+            "Module",
+            "MediaWiki",
+            "Appendix",
+            "Rhymes",
+            "Citations",
+            "Index",
+            "Thesaurus",
+            "Reconstruction",
+        ],
+        start_page: "language",
+        capitalize_first_letter: false,
+    },
+    ProjectPreset {
+        name: "wikidata",
+        host: "www.wikidata.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &[
+            "Special",
+            "File",
+            "Help",
+            "Wikidata",
+            "User",
+            "Template",
+            "Category",
+            "Module",
+            "MediaWiki",
+            "Property",
+            "Lexeme",
+            "EntitySchema",
+        ],
+        start_page: "Q575650",
+        capitalize_first_letter: true,
+    },
+    // On Commons the media and categories are the content, so File and Category are kept
+    ProjectPreset {
+        name: "commons",
+        host: "commons.wikimedia.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &[
+            "Special",
+            "Help",
+            "Commons",
+            "User",
+            "Template",
+            "Module",
+            "MediaWiki",
+            "TimedText",
+            "Creator",
+            "Institution",
+        ],
+        start_page: "Category:Rust_(programming_language)",
+        capitalize_first_letter: true,
+    },
+    ProjectPreset {
+        name: "wikivoyage",
+        host: "en.wikivoyage.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &[
+            "Special",
+            "File",
+            "Help",
+            "Wikivoyage",
+            "User",
+            "Template",
+            "Category",
+            "Module",
+            "MediaWiki",
+        ],
+        start_page: "Europe",
+        capitalize_first_letter: true,
+    },
+    ProjectPreset {
+        name: "wikiquote",
+        host: "en.wikiquote.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &DEFAULT_EXCLUDED_NAMESPACES,
+        start_page: "Albert_Einstein",
+        capitalize_first_letter: true,
+    },
+    ProjectPreset {
+        name: "wikibooks",
+        host: "en.wikibooks.org",
+        path_prefix: "/wiki/",
+        excluded_namespaces: &[
+            "Special",
+            "File",
+            "Help",
+            "Wikibooks",
+            "User",
+            "Template",
+            "Category",
+            "Module",
+            "MediaWiki",
+            "Cookbook",
+            "Wikijunior",
+            "Subject",
+            "Shelf",
+        ],
+        start_page: "Rust",
+        capitalize_first_letter: true,
+    },
+];
+
+pub fn find_project(name: &str) -> Option<&'static ProjectPreset> {
+    PROJECTS.iter().find(|project| project.name == name)
+}
+
+impl ProjectPreset {
+    pub fn start_url(&self) -> String {
+        format!(
+            "https://{}{}{}",
+            self.host, self.path_prefix, self.start_page
+        )
+    }
+}
+
+// Uses the matching preset's rules for known hosts and the Wikipedia defaults otherwise
+pub fn domain_rule(host: &str) -> DomainRule {
+    match PROJECTS.iter().find(|project| project.host == host) {
+        Some(project) => DomainRule {
+            host: host.to_string(),
+            path_prefix: project.path_prefix.to_string(),
+            excluded_namespaces: project
+                .excluded_namespaces
+                .iter()
+                .map(|namespace| namespace.to_string())
+                .collect(),
+            capitalize_first_letter: project.capitalize_first_letter,
+        },
+        None => DomainRule::new(host),
+    }
+}
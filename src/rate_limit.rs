@@ -0,0 +1,176 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Spaces requests to the same domain by `interval`, shared by all workers. Each gap is
+// randomized by up to `jitter_fraction` of the interval so workers don't fire in lockstep
+pub struct RateLimiter {
+    interval: Duration,
+    jitter_fraction: f64,
+    domain_intervals: HashMap<String, Duration>,
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration, jitter_fraction: f64) -> Self {
+        Self {
+            interval,
+            jitter_fraction,
+            domain_intervals: HashMap::new(),
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_interval(&mut self, domain: &str, interval: Duration) {
+        self.domain_intervals.insert(domain.to_string(), interval);
+    }
+
+    pub fn wait(&self, domain: &str) {
+        let interval = match self.domain_intervals.get(domain) {
+            // A robots.txt Crawl-delay is a minimum, so jitter may only lengthen it
+            Some(&interval) => interval.mul_f64(1.0 + self.jitter_fraction * random_unit()),
+            None => self
+                .interval
+                .mul_f64(1.0 + self.jitter_fraction * (2.0 * random_unit() - 1.0)),
+        };
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = match next_slot.get(domain) {
+                Some(&slot) if slot > now => slot,
+                _ => now,
+            };
+            next_slot.insert(domain.to_string(), slot + interval);
+            slot
+        };
+
+        let now = Instant::now();
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+// Caps how many requests to the same domain are in flight at once, across all workers
+pub struct DomainSlots {
+    limit: usize,
+    in_flight: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+// Holds one of a domain's slots until dropped
+pub struct DomainSlot<'a> {
+    slots: &'a DomainSlots,
+    domain: String,
+}
+
+impl DomainSlots {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            in_flight: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn acquire(&self, domain: &str) -> DomainSlot<'_> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        while in_flight.get(domain).copied().unwrap_or(0) >= self.limit {
+            in_flight = self.released.wait(in_flight).unwrap();
+        }
+        *in_flight.entry(domain.to_string()).or_insert(0) += 1;
+        DomainSlot {
+            slots: self,
+            domain: domain.to_string(),
+        }
+    }
+}
+
+impl Drop for DomainSlot<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.slots.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.domain) {
+            *count -= 1;
+        }
+        self.slots.released.notify_all();
+    }
+}
+
+// Uniform in [0, 1). RandomState is freshly keyed on every call, which is plenty for jitter
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    #[test]
+    fn in_flight_requests_never_exceed_the_cap() {
+        let slots = Arc::new(DomainSlots::new(2));
+        // (current, peak) requests in flight per domain, as a fetcher would see them
+        let in_flight = Arc::new(Mutex::new(HashMap::<&str, (usize, usize)>::new()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let slots = Arc::clone(&slots);
+                let in_flight = Arc::clone(&in_flight);
+                let domain = ["en.wikipedia.org", "de.wikipedia.org"][worker % 2];
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let _slot = slots.acquire(domain);
+                        {
+                            let mut in_flight = in_flight.lock().unwrap();
+                            let (current, peak) = in_flight.entry(domain).or_insert((0, 0));
+                            *current += 1;
+                            *peak = (*peak).max(*current);
+                        }
+                        thread::sleep(Duration::from_millis(2));
+                        in_flight.lock().unwrap().get_mut(domain).unwrap().0 -= 1;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let in_flight = in_flight.lock().unwrap();
+        assert_eq!(in_flight.len(), 2);
+        for (domain, &(current, peak)) in in_flight.iter() {
+            assert_eq!(current, 0, "{}", domain);
+            assert_eq!(peak, 2, "{}", domain);
+        }
+    }
+
+    #[test]
+    fn a_full_domain_does_not_block_other_domains() {
+        let slots = Arc::new(DomainSlots::new(1));
+        let held = slots.acquire("en.wikipedia.org");
+
+        let (sender, receiver) = mpsc::channel();
+        let other = Arc::clone(&slots);
+        thread::spawn(move || {
+            let _slot = other.acquire("de.wikipedia.org");
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+
+        // The held domain itself stays blocked until the slot is released
+        let (sender, receiver) = mpsc::channel();
+        let same = Arc::clone(&slots);
+        thread::spawn(move || {
+            let _slot = same.acquire("en.wikipedia.org");
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(held);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::utils::{fetch_page, FetchError, HttpClient};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct RobotsRules {
+    pub crawl_delay: Option<Duration>,
+}
+
+pub fn fetch_robots_rules(client: &HttpClient, domain: &str, max_page_bytes: u64) -> RobotsRules {
+    let url = format!("https://{}/robots.txt", domain);
+    match fetch_page(client, &url, max_page_bytes) {
+        Ok(page) => parse_robots(&page.body),
+        // No robots.txt means no restrictions
+        Err(FetchError::Status(StatusCode::NOT_FOUND)) => RobotsRules::default(),
+        Err(err) => {
+            eprintln!("Failed to fetch {}: {}", url, err);
+            RobotsRules::default()
+        }
+    }
+}
+
+// Only the directives in groups addressed to every user agent ("*") are honored
+pub fn parse_robots(content: &str) -> RobotsRules {
+    let mut rules = RobotsRules::default();
+    let mut group_applies = false;
+    let mut reading_agents = false;
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+
+        match key.as_str() {
+            "user-agent" => {
+                if !reading_agents {
+                    group_applies = false;
+                }
+                reading_agents = true;
+                group_applies |= value == "*";
+            }
+            "crawl-delay" => {
+                reading_agents = false;
+                if group_applies {
+                    if let Ok(seconds) = value.parse::<f64>() {
+                        if seconds.is_finite() && seconds >= 0.0 {
+                            rules.crawl_delay = Some(Duration::from_secs_f64(seconds));
+                        }
+                    }
+                }
+            }
+            _ => reading_agents = false,
+        }
+    }
+
+    rules
+}
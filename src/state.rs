@@ -1,29 +1,254 @@
+use crate::filter::{LinkDecision, TitlePatternMode, URLFilter};
+use crate::frontier::{retain_segment, Segment};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const STATE_FILE: &str = "crawl_state.json";
+pub const VISITED_FILE: &str = "visited_pages.json";
+pub const FRONTIER_DIR: &str = "frontier"; // Spilled queue segments, inside the output directory
 
 #[derive(Serialize, Deserialize)]
 pub struct CrawlState {
     pub queue: Vec<(String, usize)>, // (URL, depth)
+    #[serde(default)]
+    pub queue_segments: Vec<Segment>, // The rest of the queue, spilled to disk
     pub visited: Vec<String>,
+    #[serde(default)]
+    pub depths: HashMap<String, usize>, // Minimum depth each URL was queued at
+    #[serde(default)]
+    pub fetched: Vec<String>, // Subset of `visited` that was actually crawled
+    #[serde(default)]
+    pub user_agent: Option<String>, // Identity of the crawler that wrote this state
+    #[serde(default)]
+    pub depth_limit: Option<usize>, // Depths are stored as this limit minus the remaining budget
+}
+
+impl CrawlState {
+    // Re-expresses restored depths against the current session's depth limit, so every queued URL
+    // keeps the remaining depth budget it had when the state was saved
+    pub fn rebase_depths(&mut self, depth_limit: usize) {
+        let Some(saved_limit) = self.depth_limit else {
+            return;
+        };
+        let shift = depth_limit as isize - saved_limit as isize;
+        for (_, depth) in &mut self.queue {
+            *depth = depth.saturating_add_signed(shift);
+        }
+        for depth in self.depths.values_mut() {
+            *depth = depth.saturating_add_signed(shift);
+        }
+        for segment in &mut self.queue_segments {
+            segment.depth_shift += shift;
+        }
+        self.depth_limit = Some(depth_limit);
+    }
+
+    // Drops restored URLs that the current filter would no longer accept, rewriting the spilled
+    // queue segments in `frontier_dir` too. Returns how many visited and queued URLs were pruned.
+    pub fn reconcile(
+        &mut self,
+        filter: &URLFilter,
+        frontier_dir: &Path,
+    ) -> io::Result<(usize, usize)> {
+        let visited_before = self.visited.len();
+        self.visited.retain(|url| {
+            matches!(
+                filter.classify_url(url),
+                LinkDecision::Follow(_)
+                    | LinkDecision::TitlePattern(
+                        TitlePatternMode::Include | TitlePatternMode::NoExpand,
+                        _
+                    )
+            )
+        });
+
+        let kept: HashSet<&String> = self.visited.iter().collect();
+        self.depths.retain(|url, _| kept.contains(url));
+        self.fetched.retain(|url| kept.contains(url));
+
+        let still_queued = |(url, _): &(String, usize)| {
+            matches!(
+                filter.classify_url(url),
+                LinkDecision::Follow(_) | LinkDecision::TitlePattern(TitlePatternMode::Include, _)
+            )
+        };
+        let queued_before = self.queue.len();
+        self.queue.retain(still_queued);
+        let mut pruned_queued = queued_before - self.queue.len();
+        for segment in &mut self.queue_segments {
+            pruned_queued += retain_segment(frontier_dir, segment, still_queued)?;
+        }
+        self.queue_segments.retain(|segment| segment.entries > 0);
+
+        Ok((visited_before - self.visited.len(), pruned_queued))
+    }
 }
 
-pub fn save_state(state: &CrawlState) -> io::Result<()> {
+// The most recently written state file in `out_dir`. Timestamped checkpoints leave older states
+// behind whose segments may already have been consumed, so only the newest is safe to resume.
+pub fn latest_state_path(out_dir: &Path) -> PathBuf {
+    let stem = STATE_FILE.trim_end_matches(".json");
+    fs::read_dir(out_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name == STATE_FILE
+                || (name.starts_with(&format!("{}.", stem))
+                    && name.ends_with(".json")
+                    && !name.ends_with(".manifest.json"))
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max()
+        .map(|(_, path)| path)
+        .unwrap_or_else(|| out_dir.join(STATE_FILE))
+}
+
+pub fn save_state(state: &CrawlState, path: &Path) -> io::Result<()> {
     let serialized = serde_json::to_string(state)?;
-    let mut file = File::create("crawl_state.json")?;
+    let mut file = File::create(path)?;
     file.write_all(serialized.as_bytes())?;
     Ok(())
 }
 
-pub fn load_state() -> io::Result<CrawlState> {
-    let file = File::open("crawl_state.json")?;
+pub fn load_state(path: &Path) -> io::Result<CrawlState> {
+    let file = File::open(path)?;
     let state: CrawlState = serde_json::from_reader(file)?;
     Ok(state)
 }
 
-pub fn save_visited(visited: &Vec<String>) -> io::Result<()> {
+pub fn save_visited(visited: &Vec<String>, path: &Path) -> io::Result<()> {
     let serialized = serde_json::to_string(visited)?;
-    let mut file = File::create("visited_pages.json")?;
+    let mut file = File::create(path)?;
     file.write_all(serialized.as_bytes())?;
     Ok(())
 }
+
+// Writes the sorted crawled URLs, one per line for a .txt path and as a JSON array otherwise
+pub fn export_visited(fetched: &HashSet<String>, path: &Path) -> io::Result<()> {
+    let mut urls: Vec<&String> = fetched.iter().collect();
+    urls.sort();
+
+    let mut file = File::create(path)?;
+    if path.extension().is_some_and(|extension| extension == "txt") {
+        for url in urls {
+            writeln!(file, "{}", url)?;
+        }
+    } else {
+        file.write_all(serde_json::to_string_pretty(&urls)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn saved_state(depth_limit: Option<usize>) -> CrawlState {
+        CrawlState {
+            queue: vec![("https://en.wikipedia.org/wiki/Topology".to_string(), 2)],
+            queue_segments: vec![Segment {
+                file: "segment-000001.jsonl".to_string(),
+                entries: 10,
+                depth_shift: 0,
+            }],
+            visited: Vec::new(),
+            depths: HashMap::from([
+                ("https://en.wikipedia.org/wiki/Graph_theory".to_string(), 0),
+                ("https://en.wikipedia.org/wiki/Topology".to_string(), 2),
+            ]),
+            fetched: Vec::new(),
+            user_agent: None,
+            depth_limit,
+        }
+    }
+
+    #[test]
+    fn rebase_depths_keeps_the_remaining_budget() {
+        let mut state = saved_state(Some(3));
+        state.rebase_depths(5);
+        assert_eq!(state.queue[0].1, 4);
+        assert_eq!(
+            state.depths["https://en.wikipedia.org/wiki/Graph_theory"],
+            2
+        );
+        assert_eq!(state.depths["https://en.wikipedia.org/wiki/Topology"], 4);
+        assert_eq!(state.queue_segments[0].depth_shift, 2);
+        assert_eq!(state.depth_limit, Some(5));
+
+        // Segments not yet read keep accumulating shifts across sessions
+        state.rebase_depths(4);
+        assert_eq!(state.queue[0].1, 3);
+        assert_eq!(state.queue_segments[0].depth_shift, 1);
+    }
+
+    #[test]
+    fn rebase_depths_clamps_budgets_beyond_a_smaller_limit() {
+        let mut state = saved_state(Some(3));
+        state.rebase_depths(1);
+        // Graph_theory had 3 hops left, more than the new limit allows: it starts from the top
+        assert_eq!(
+            state.depths["https://en.wikipedia.org/wiki/Graph_theory"],
+            0
+        );
+        assert_eq!(state.depths["https://en.wikipedia.org/wiki/Topology"], 0);
+        assert_eq!(state.queue[0].1, 0);
+        assert_eq!(state.queue_segments[0].depth_shift, -2);
+    }
+
+    #[test]
+    fn rebase_depths_leaves_states_without_a_limit_alone() {
+        let mut state = saved_state(None);
+        state.rebase_depths(6);
+        assert_eq!(state.queue[0].1, 2);
+        assert_eq!(state.depths["https://en.wikipedia.org/wiki/Topology"], 2);
+        assert_eq!(state.depth_limit, None);
+    }
+
+    fn crawled() -> HashSet<String> {
+        ["Topology", "Graph_theory", "Algebra"]
+            .iter()
+            .map(|title| format!("https://en.wikipedia.org/wiki/{}", title))
+            .collect()
+    }
+
+    #[test]
+    fn export_visited_writes_sorted_json() {
+        let dir = temp_dir("export-json");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crawled.json");
+        export_visited(&crawled(), &path).unwrap();
+
+        let urls: Vec<String> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            urls,
+            [
+                "https://en.wikipedia.org/wiki/Algebra",
+                "https://en.wikipedia.org/wiki/Graph_theory",
+                "https://en.wikipedia.org/wiki/Topology",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn export_visited_writes_sorted_lines_for_txt() {
+        let dir = temp_dir("export-txt");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crawled.txt");
+        export_visited(&crawled(), &path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "https://en.wikipedia.org/wiki/Algebra\n\
+             https://en.wikipedia.org/wiki/Graph_theory\n\
+             https://en.wikipedia.org/wiki/Topology\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
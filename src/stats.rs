@@ -1,11 +1,34 @@
+use crate::timing::WorkerTimings;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+const TOP_OUT_DEGREE: usize = 5;
+
 #[derive(Serialize, Debug, Deserialize)]
 pub struct CrawlStats {
     pub pages_visited: usize,
+    pub pages_reexpanded: usize, // Revisited at a smaller depth than before
     pub links_followed: usize,
     pub links_ignored: usize,
+    pub title_pattern_included: usize,
+    pub title_pattern_boundary: usize,
+    pub title_pattern_excluded: usize,
+    pub noise_links_ignored: usize,
+    pub suspicious_zero_link_pages: usize, // Fetched fine but yielded no followable links
+    pub api_fallback_pages: usize,         // Links recovered through the MediaWiki API instead
+    pub duplicate_links_skipped: usize,    // Already queued or visited, so not enqueued again
+    pub spider_trap_links: HashMap<String, usize>, // Links dropped per flagged title pattern
+    pub rejected_links: HashMap<String, usize>, // Links rejected by the filter, per reason
+    pub namespace_links: HashMap<String, usize>, // Links into excluded namespaces, per namespace
+    pub fetch_errors: HashMap<String, usize>, // By kind of failure
+    pub pages_skipped_too_large: usize,
+    pub stub_pages_skipped: usize, // Below the minimum prose length
+    pub invalid_pages: HashMap<String, usize>, // Not an article page, by reason
+    pub requests_short_circuited: usize,
+    pub pages_per_domain: HashMap<String, usize>,
+    pub top_out_degree: Vec<(String, usize)>, // Pages with the most accepted links, highest first
+    pub timings: Vec<WorkerTimings>,
     pub start_time: u64, // Time in milliseconds since UNIX_EPOCH
 }
 
@@ -13,14 +36,48 @@ impl CrawlStats {
     pub fn new() -> Self {
         Self {
             pages_visited: 0,
+            pages_reexpanded: 0,
             links_followed: 0,
             links_ignored: 0,
+            title_pattern_included: 0,
+            title_pattern_boundary: 0,
+            title_pattern_excluded: 0,
+            noise_links_ignored: 0,
+            suspicious_zero_link_pages: 0,
+            api_fallback_pages: 0,
+            duplicate_links_skipped: 0,
+            spider_trap_links: HashMap::new(),
+            rejected_links: HashMap::new(),
+            namespace_links: HashMap::new(),
+            fetch_errors: HashMap::new(),
+            pages_skipped_too_large: 0,
+            stub_pages_skipped: 0,
+            invalid_pages: HashMap::new(),
+            requests_short_circuited: 0,
+            pages_per_domain: HashMap::new(),
+            top_out_degree: Vec::new(),
+            timings: Vec::new(),
             start_time: current_time_millis(),
         }
     }
+
+    pub fn record_out_degree(&mut self, url: &str, links: usize) {
+        if self.top_out_degree.len() == TOP_OUT_DEGREE
+            && self
+                .top_out_degree
+                .last()
+                .is_some_and(|&(_, lowest)| links <= lowest)
+        {
+            return;
+        }
+        self.top_out_degree.push((url.to_string(), links));
+        self.top_out_degree
+            .sort_by_key(|&(_, links)| std::cmp::Reverse(links));
+        self.top_out_degree.truncate(TOP_OUT_DEGREE);
+    }
 }
 
-fn current_time_millis() -> u64 {
+pub fn current_time_millis() -> u64 {
     let now = SystemTime::now();
     let duration = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
     duration.as_millis() as u64
@@ -0,0 +1,236 @@
+use crate::stats::{current_time_millis, CrawlStats};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const SUMMARY_FILE: &str = "summary.json";
+
+// End-of-crawl report, built only from counters the crawl already tracks
+#[derive(Serialize)]
+pub struct CrawlSummary {
+    pub outcome: String,
+    pub elapsed_secs: f64,
+    pub pages_per_sec: f64,
+    pub pages_fetched: usize,
+    pub pages_reexpanded: usize,
+    pub pages_known: usize, // Every URL recorded, fetched or not
+    // pages_known split into pages fetched in any session and pages only ever linked to
+    pub pages_fetched_total: usize,
+    pub pages_discovered_only: usize,
+    pub pages_queued: usize,
+    pub links_followed: usize,
+    pub links_ignored: usize,
+    pub title_pattern_included: usize,
+    pub title_pattern_boundary: usize,
+    pub suspicious_zero_link_pages: usize,
+    pub api_fallback_pages: usize,
+    // Depths are budget-relative: the depth limit minus the remaining budget, so a URL one link
+    // from a `--seed URL 1` seed sits at depth_limit rather than 1
+    pub depth_limit: usize,
+    pub urls_per_relative_depth: BTreeMap<usize, usize>,
+    pub errors: BTreeMap<String, usize>,
+    pub rejections: BTreeMap<String, usize>,
+    pub rejected_namespaces: BTreeMap<String, usize>,
+    pub spider_trap_links: BTreeMap<String, usize>, // Per title pattern
+    pub pages_per_domain: BTreeMap<String, usize>,
+    pub top_out_degree: Vec<(String, usize)>,
+}
+
+impl CrawlSummary {
+    pub fn new(
+        outcome: String,
+        stats: &CrawlStats,
+        depths: &HashMap<String, usize>,
+        depth_limit: usize,
+        pages_known: usize,
+        pages_fetched_total: usize,
+        pages_queued: usize,
+    ) -> Self {
+        let elapsed_secs = current_time_millis().saturating_sub(stats.start_time) as f64 / 1000.0;
+        let pages_per_sec = if elapsed_secs > 0.0 {
+            stats.pages_visited as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        let mut urls_per_relative_depth = BTreeMap::new();
+        for &depth in depths.values() {
+            *urls_per_relative_depth.entry(depth).or_insert(0) += 1;
+        }
+
+        let mut errors: BTreeMap<String, usize> = stats
+            .fetch_errors
+            .iter()
+            .map(|(kind, &count)| (kind.clone(), count))
+            .collect();
+        for (reason, &count) in &stats.invalid_pages {
+            errors.insert(format!("invalid: {}", reason), count);
+        }
+        errors.insert("page too large".to_string(), stats.pages_skipped_too_large);
+        errors.insert(
+            "circuit open (requeued)".to_string(),
+            stats.requests_short_circuited,
+        );
+        errors.insert("stub skipped".to_string(), stats.stub_pages_skipped);
+        errors.retain(|_, count| *count > 0);
+
+        let spider_trap_links: usize = stats.spider_trap_links.values().sum();
+        let mut rejections = BTreeMap::new();
+        rejections.insert("noise page".to_string(), stats.noise_links_ignored);
+        rejections.insert(
+            "title pattern excluded".to_string(),
+            stats.title_pattern_excluded,
+        );
+        rejections.insert("already known".to_string(), stats.duplicate_links_skipped);
+        rejections.insert("spider trap".to_string(), spider_trap_links);
+        for (reason, &count) in &stats.rejected_links {
+            rejections.insert(reason.clone(), count);
+        }
+        rejections.retain(|_, count| *count > 0);
+
+        Self {
+            outcome,
+            elapsed_secs,
+            pages_per_sec,
+            pages_fetched: stats.pages_visited,
+            pages_reexpanded: stats.pages_reexpanded,
+            pages_known,
+            pages_fetched_total,
+            pages_discovered_only: pages_known.saturating_sub(pages_fetched_total),
+            pages_queued,
+            links_followed: stats.links_followed,
+            links_ignored: stats.links_ignored,
+            title_pattern_included: stats.title_pattern_included,
+            title_pattern_boundary: stats.title_pattern_boundary,
+            suspicious_zero_link_pages: stats.suspicious_zero_link_pages,
+            api_fallback_pages: stats.api_fallback_pages,
+            depth_limit,
+            urls_per_relative_depth,
+            errors,
+            rejections,
+            rejected_namespaces: stats
+                .namespace_links
+                .iter()
+                .map(|(namespace, &count)| (namespace.clone(), count))
+                .collect(),
+            spider_trap_links: stats
+                .spider_trap_links
+                .iter()
+                .map(|(pattern, &count)| (pattern.clone(), count))
+                .collect(),
+            pages_per_domain: stats
+                .pages_per_domain
+                .iter()
+                .map(|(domain, &count)| (domain.clone(), count))
+                .collect(),
+            top_out_degree: stats.top_out_degree.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CrawlSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Crawl summary ({})", self.outcome)?;
+        let elapsed = format!("{:.1}s", self.elapsed_secs);
+        writeln!(f, "  {:<32} {:>10}", "elapsed", elapsed)?;
+        writeln!(
+            f,
+            "  {:<32} {:>10.2}",
+            "pages per second", self.pages_per_sec
+        )?;
+        for (label, value) in [
+            ("pages fetched", self.pages_fetched),
+            ("pages re-expanded", self.pages_reexpanded),
+            ("pages known", self.pages_known),
+            ("  fetched, all sessions", self.pages_fetched_total),
+            ("  only linked to", self.pages_discovered_only),
+            ("pages still queued", self.pages_queued),
+            ("links followed", self.links_followed),
+            ("links ignored", self.links_ignored),
+            ("title pattern links included", self.title_pattern_included),
+            ("title pattern boundary links", self.title_pattern_boundary),
+            ("pages with no valid links", self.suspicious_zero_link_pages),
+            ("pages using API link fallback", self.api_fallback_pages),
+        ] {
+            writeln!(f, "  {:<32} {:>10}", label, value)?;
+        }
+
+        writeln!(
+            f,
+            "URLs per depth (depth limit {} minus remaining budget)",
+            self.depth_limit
+        )?;
+        for (depth, count) in &self.urls_per_relative_depth {
+            writeln!(f, "  {:<32} {:>10}", depth, count)?;
+        }
+        write_section(f, "Errors", &self.errors)?;
+        write_section(f, "Rejected links", &self.rejections)?;
+        write_section(f, "Rejected links per namespace", &self.rejected_namespaces)?;
+        write_section(f, "Spider trap patterns", &self.spider_trap_links)?;
+        if self.pages_per_domain.len() > 1 {
+            write_section(f, "Pages per domain", &self.pages_per_domain)?;
+        }
+
+        writeln!(f, "Highest out-degree pages")?;
+        for (url, links) in &self.top_out_degree {
+            writeln!(f, "  {:<32} {:>10}", url, links)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_section(
+    f: &mut fmt::Formatter,
+    title: &str,
+    rows: &BTreeMap<String, usize>,
+) -> fmt::Result {
+    writeln!(f, "{}", title)?;
+    if rows.is_empty() {
+        writeln!(f, "  none")?;
+    }
+    for (label, count) in rows {
+        writeln!(f, "  {:<32} {:>10}", label, count)?;
+    }
+    Ok(())
+}
+
+pub fn save_summary(summary: &CrawlSummary, path: &Path) -> io::Result<()> {
+    let serialized = serde_json::to_string_pretty(summary)?;
+    let mut file = File::create(path)?;
+    file.write_all(serialized.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejections_are_broken_down_by_reason_and_namespace() {
+        let mut stats = CrawlStats::new();
+        stats.links_ignored = 6;
+        stats.duplicate_links_skipped = 1;
+        stats
+            .rejected_links
+            .insert("excluded namespace".to_string(), 4);
+        stats.rejected_links.insert("off-domain".to_string(), 1);
+        stats.namespace_links.insert("Talk".to_string(), 3);
+        stats.namespace_links.insert("File".to_string(), 1);
+
+        let summary = CrawlSummary::new("done".to_string(), &stats, &HashMap::new(), 3, 10, 4, 0);
+        assert_eq!(summary.rejections["excluded namespace"], 4);
+        assert_eq!(summary.rejections["off-domain"], 1);
+        assert_eq!(summary.rejections["already known"], 1);
+        assert_eq!(summary.rejected_namespaces["Talk"], 3);
+        assert_eq!(summary.pages_discovered_only, 6);
+
+        let rendered = summary.to_string();
+        assert!(rendered.contains("Rejected links per namespace"));
+        assert!(rendered.contains(&format!("  {:<32} {:>10}", "Talk", 3)));
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["rejected_namespaces"]["File"], 1);
+    }
+}
@@ -0,0 +1,10 @@
+use std::fs;
+use std::path::PathBuf;
+
+// An empty directory under the system temp dir, unique to this test run and `name`
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("wikipedia-mapper-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
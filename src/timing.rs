@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    RateLimitWait,
+    DomainSlotWait, // Waiting for the per-domain concurrency cap
+    Network,
+    Parsing,
+    LockWait,
+    QueueIdle,
+}
+
+// Per-worker nanosecond counters, one per phase
+#[derive(Default)]
+pub struct PhaseTimers {
+    nanos: [AtomicU64; 6],
+}
+
+impl PhaseTimers {
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    pub fn record(&self, phase: Phase, elapsed: Duration) {
+        self.nanos[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, worker: usize, pages: usize) -> WorkerTimings {
+        let nanos = |phase: Phase| self.nanos[phase as usize].load(Ordering::Relaxed);
+        WorkerTimings {
+            worker,
+            pages,
+            rate_limit_wait_ns: nanos(Phase::RateLimitWait),
+            domain_slot_wait_ns: nanos(Phase::DomainSlotWait),
+            network_ns: nanos(Phase::Network),
+            parsing_ns: nanos(Phase::Parsing),
+            lock_wait_ns: nanos(Phase::LockWait),
+            queue_idle_ns: nanos(Phase::QueueIdle),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Deserialize, Clone)]
+pub struct WorkerTimings {
+    pub worker: usize,
+    pub pages: usize,
+    pub rate_limit_wait_ns: u64,
+    pub domain_slot_wait_ns: u64,
+    pub network_ns: u64,
+    pub parsing_ns: u64,
+    pub lock_wait_ns: u64,
+    pub queue_idle_ns: u64,
+}
+
+impl WorkerTimings {
+    fn phases(&self) -> [u64; 6] {
+        [
+            self.rate_limit_wait_ns,
+            self.domain_slot_wait_ns,
+            self.network_ns,
+            self.parsing_ns,
+            self.lock_wait_ns,
+            self.queue_idle_ns,
+        ]
+    }
+}
+
+pub fn print_timing_table(timings: &[WorkerTimings]) {
+    let seconds = |nanos: u64| nanos as f64 / 1e9;
+
+    println!(
+        "{:<8} {:>6} {:>11} {:>11} {:>11} {:>11} {:>11} {:>11}",
+        "worker",
+        "pages",
+        "rate limit",
+        "host slots",
+        "network",
+        "parsing",
+        "lock wait",
+        "queue idle"
+    );
+    let mut totals = [0u64; 6];
+    for timing in timings {
+        let phases = timing.phases();
+        println!(
+            "{:<8} {:>6} {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s",
+            timing.worker,
+            timing.pages,
+            seconds(phases[0]),
+            seconds(phases[1]),
+            seconds(phases[2]),
+            seconds(phases[3]),
+            seconds(phases[4]),
+            seconds(phases[5])
+        );
+        for (total, nanos) in totals.iter_mut().zip(phases) {
+            *total += nanos;
+        }
+    }
+
+    let pages: usize = timings.iter().map(|timing| timing.pages).sum();
+    println!(
+        "{:<8} {:>6} {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s {:>10.2}s",
+        "total",
+        pages,
+        seconds(totals[0]),
+        seconds(totals[1]),
+        seconds(totals[2]),
+        seconds(totals[3]),
+        seconds(totals[4]),
+        seconds(totals[5])
+    );
+    if pages > 0 {
+        let per_page_ms = |nanos: u64| nanos as f64 / 1e6 / pages as f64;
+        println!(
+            "{:<8} {:>6} {:>9.1}ms {:>9.1}ms {:>9.1}ms {:>9.1}ms {:>9.1}ms {:>9.1}ms",
+            "per page",
+            "",
+            per_page_ms(totals[0]),
+            per_page_ms(totals[1]),
+            per_page_ms(totals[2]),
+            per_page_ms(totals[3]),
+            per_page_ms(totals[4]),
+            per_page_ms(totals[5])
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::filter::MONTHS;
+use std::collections::{HashMap, HashSet};
+
+const TRAP_RATIO: f64 = 10.0; // How far a pattern must outpace the average of the others
+
+// Flags title patterns that are discovered far more often than the rest of the crawl, the
+// signature of calendar pages and template-generated sequences
+pub struct TrapDetector {
+    min_discoveries: usize,
+    discoveries: HashMap<String, usize>,
+    total: usize,
+    flagged: HashSet<String>,
+}
+
+impl TrapDetector {
+    pub fn new(min_discoveries: usize) -> Self {
+        Self {
+            min_discoveries,
+            discoveries: HashMap::new(),
+            total: 0,
+            flagged: HashSet::new(),
+        }
+    }
+
+    // Records a newly discovered title. Returns its pattern when that pattern is a trap, in
+    // which case the title should not be enqueued.
+    pub fn check(&mut self, title: &str) -> Option<String> {
+        let pattern = title_pattern(title)?;
+        if self.flagged.contains(&pattern) {
+            return Some(pattern);
+        }
+
+        let count = self.discoveries.entry(pattern.clone()).or_insert(0);
+        *count += 1;
+        let count = *count;
+        self.total += 1;
+        if count < self.min_discoveries {
+            return None;
+        }
+
+        let others = self.discoveries.len() - 1;
+        let others_average = if others == 0 {
+            0.0
+        } else {
+            (self.total - count) as f64 / others as f64
+        };
+        if count as f64 >= TRAP_RATIO * others_average {
+            eprintln!(
+                "Spider trap suspected: '{}' discovered {} times, capping it",
+                pattern, count
+            );
+            self.flagged.insert(pattern);
+        }
+        None
+    }
+}
+
+// Replaces numbers and month names so "2019 in film", "1987 in film" and "March 2020" style
+// titles collapse into one pattern. Titles without either have no pattern.
+pub fn title_pattern(title: &str) -> Option<String> {
+    let mut pattern = String::with_capacity(title.len());
+    let mut changed = false;
+    let mut chars = title.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            while chars.peek().is_some_and(|next| next.is_ascii_digit()) {
+                chars.next();
+            }
+            pattern.push('#');
+            changed = true;
+        } else {
+            pattern.push(c);
+        }
+    }
+
+    let words: Vec<&str> = pattern
+        .split(' ')
+        .map(|word| {
+            if MONTHS.contains(&word) {
+                changed = true;
+                "<month>"
+            } else {
+                word
+            }
+        })
+        .collect();
+
+    changed.then(|| words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_pattern_collapses_numbers_and_months() {
+        for (title, pattern) in [
+            ("2019 in film", "# in film"),
+            ("1987 in film", "# in film"),
+            ("March 2020", "<month> #"),
+            ("Deaths in January 1999", "Deaths in <month> #"),
+            ("Interstate 95 exit 12", "Interstate # exit #"),
+        ] {
+            assert_eq!(title_pattern(title).as_deref(), Some(pattern), "{}", title);
+        }
+        assert_eq!(title_pattern("Graph theory"), None);
+        assert_eq!(title_pattern("Marching band"), None);
+    }
+
+    #[test]
+    fn patterns_are_flagged_only_past_the_threshold() {
+        let mut traps = TrapDetector::new(5);
+        for year in 0..5 {
+            assert_eq!(traps.check(&format!("{} in film", 1900 + year)), None);
+        }
+        // Flagged on the fifth discovery, capped from the sixth on
+        assert_eq!(traps.check("1999 in film").as_deref(), Some("# in film"));
+        assert_eq!(traps.check("Graph theory"), None);
+    }
+
+    #[test]
+    fn patterns_must_outpace_the_others_by_the_ratio() {
+        let mut traps = TrapDetector::new(5);
+        // Three ordinary patterns with five discoveries each
+        for round in 0..5 {
+            for topic in ["Apollo", "Route", "Symphony No."] {
+                assert_eq!(traps.check(&format!("{} {}", topic, round)), None);
+            }
+        }
+
+        // Flagged once it reaches ten times the others' average of five
+        for year in 0..50 {
+            assert_eq!(traps.check(&format!("{} in film", 1900 + year)), None);
+        }
+        assert!(traps.check("1950 in film").is_some());
+        assert_eq!(traps.check("Apollo 11"), None);
+    }
+
+    #[test]
+    fn flagged_patterns_stay_flagged() {
+        let mut traps = TrapDetector::new(2);
+        traps.check("January 2001");
+        traps.check("February 2001");
+        assert!(traps.check("March 2001").is_some());
+
+        // Even once the rest of the crawl catches up
+        for round in 0..100 {
+            traps.check(&format!("Apollo {}", round));
+            traps.check(&format!("Route {}", round));
+        }
+        assert_eq!(traps.check("April 2001").as_deref(), Some("<month> #"));
+    }
+}